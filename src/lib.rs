@@ -2,6 +2,27 @@
 
 mod server;
 
-pub use server::client::{Client, ClientConnectionError, ClientType, Publisher, Subscriber};
-pub use server::connection_message::ConnectionMessage;
-pub use server::Server;
+pub use server::acl::{AclConfig, AclDefault, AclRule, Permission};
+pub use server::admin_message::AdminMessage;
+pub use server::auth::{
+    hash_password, AuthConfig, Authenticator, Credentials, PasswordAuthenticator,
+    TokenAuthenticator,
+};
+pub use server::client::{Admin, Client, ClientConnectionError, ClientType, Publisher, Subscriber};
+pub use server::config::{ConfigLoader, Limits, ListenerConfig, ServerConfig};
+pub use server::connection_message::{AckMode, ConnectionMessage, MessageFormat, StartPosition};
+pub use server::framing::Framing;
+pub use server::publisher_message::PublisherMessage;
+pub use server::reload::ReloadHandle;
+pub use server::service_message::{
+    Ack, AckResult, ClientInfo, ClientSummary, ConfigChanges, ConnectionDescription,
+    DisconnectReason, ErrorCode, PublishError, Role, ServiceMessage, TopicClients,
+    TopicDescription,
+};
+pub use server::shutdown::ShutdownHandle;
+pub use server::stream::{ClientAddr, ClientStream, PeerCredentials};
+pub use server::subscriber_message::SubscriberMessage;
+pub use server::tls::TlsConfig;
+pub use server::topic::config::{Retention, SlowConsumerPolicy, TopicConfig, TopicsConfig};
+pub use server::unix::UnixListenerConfig;
+pub use server::{Server, DEFAULT_SHUTDOWN_TIMEOUT};
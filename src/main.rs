@@ -1,51 +1,232 @@
-mod server;
-
-use server::Server;
+use simple_kafka::{hash_password, Server, ServerConfig, TopicsConfig, UnixListenerConfig};
 use simplelog::*;
 use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::io;
 
 use clap::Parser;
-use log::error;
+use log::{error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// TOML configuration file of the server.
+    /// Other arguments override its values.
+    ///
+    /// Example: ./simple-kafka.toml
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Validate the configuration and exit.
+    #[arg(long)]
+    check_config: bool,
+
+    /// Read a password from stdin, print its hash for the users file and exit.
+    #[arg(long)]
+    hash_password: bool,
+
     /// Server ip address.
     ///
     /// Example: 127.0.0.1
     #[arg(long)]
-    address: IpAddr,
+    address: Option<IpAddr>,
 
     /// Server port.
     ///
     /// Example: 7000
     #[arg(long)]
-    port: u16,
+    port: Option<u16>,
+
+    /// Path of a Unix domain socket accepting clients on the same host, in addition to TCP.
+    /// A stale socket file is removed at startup.
+    ///
+    /// Example: /run/simple-kafka/simple-kafka.sock
+    #[arg(long)]
+    unix_listener: Option<PathBuf>,
+
+    /// Address of the HTTP endpoint with health checks at /healthz and /readyz
+    /// and Prometheus metrics at /metrics.
+    /// Without it the endpoint is disabled.
+    ///
+    /// Example: 127.0.0.1:9100
+    #[arg(long)]
+    http_listener: Option<SocketAddr>,
+
+    /// Log level: off, error, warn, info, debug or trace.
+    ///
+    /// Example: debug
+    #[arg(long)]
+    log_level: Option<LevelFilter>,
+
+    /// Directory where topic logs are stored.
+    /// Without it messages are kept in memory only.
+    ///
+    /// Example: ./data
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Deprecated: use topic settings of the configuration file instead.
+    /// JSON file with the default topic config and topics declared in advance.
+    /// Replaces all topic settings of the configuration file
+    /// (`default`, `topics`, `topic_name_pattern` and `auto_create`).
+    ///
+    /// Example: ./topics.json
+    #[arg(long)]
+    topics_config: Option<PathBuf>,
+}
+
+impl Args {
+    /// Loads the configuration file (if any) and applies arguments on top of it
+    fn server_config(&self) -> io::Result<ServerConfig> {
+        let mut config = match &self.config {
+            Some(path) => ServerConfig::load(path).map_err(|e| {
+                io::Error::new(e.kind(), format!("config {} -- {}", path.display(), e))
+            })?,
+            None => ServerConfig::default(),
+        };
+        if let Some(address) = self.address {
+            config.listener.address = address;
+        }
+        if let Some(port) = self.port {
+            config.listener.port = port;
+        }
+        if let Some(path) = &self.unix_listener {
+            let mode = config
+                .unix_listener
+                .and_then(|unix_listener| unix_listener.mode);
+            config.unix_listener = Some(UnixListenerConfig {
+                path: path.clone(),
+                mode,
+            });
+        }
+        if let Some(http_listener) = self.http_listener {
+            config.http_listener = Some(http_listener);
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(data_dir) = &self.data_dir {
+            config.data_dir = Some(data_dir.clone());
+        }
+        if let Some(path) = &self.topics_config {
+            config.topics = TopicsConfig::load(path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("topics config {} -- {}", path.display(), e),
+                )
+            })?;
+        }
+        Ok(config)
+    }
 }
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
+    // the logger passes all levels, the level of the configuration is applied with `log::set_max_level`
     TermLogger::init(
-        LevelFilter::Info,
+        LevelFilter::Trace,
         Config::default(),
         TerminalMode::Stderr,
         ColorChoice::Auto,
     )
     .unwrap();
+    log::set_max_level(LevelFilter::Info);
 
-    let args = Args::parse();
+    let args = Arc::new(Args::parse());
 
-    let socket_address = SocketAddr::new(args.address, args.port);
+    if args.hash_password {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        println!("{}", hash_password(password.trim_end_matches(['\r', '\n'])));
+        return Ok(());
+    }
 
-    let mut server = match Server::new(&socket_address).await {
+    let config = match args.server_config() {
         Err(e) => {
-            error!("Not able to start server on {} -- {}", socket_address, e);
+            error!("Not able to load {}", e);
+            return Err(e);
+        }
+        Ok(config) => config,
+    };
+    log::set_max_level(config.log_level);
+    if args.topics_config.is_some() {
+        warn!(
+            "--topics-config is deprecated, use topic settings of the configuration file instead"
+        );
+    }
+    if args.check_config {
+        // the users file and TLS certificates are loaded only when the server starts
+        if let Err(e) = config.auth.authenticators() {
+            error!("Not able to load {}", e);
+            return Err(e);
+        }
+        if let Some(Err(e)) = config.tls.as_ref().map(|tls| tls.acceptor()) {
+            error!("Not able to load {}", e);
+            return Err(e);
+        }
+        info!("Configuration is valid");
+        return Ok(());
+    }
+
+    let mut server = match Server::from_config(&config).await {
+        Err(e) => {
+            error!("Not able to start server -- {}", e);
             return Err(e);
         }
         Ok(server) => server,
     };
+    server = server.with_config_loader(move || args.server_config());
+
+    #[cfg(unix)]
+    {
+        let reload = server.reload_handle();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangup) => {
+                    while hangup.recv().await.is_some() {
+                        info!("Received SIGHUP, reloading configuration");
+                        reload.reload();
+                    }
+                }
+                Err(e) => error!("Not able to listen for SIGHUP -- {}", e),
+            }
+        });
+    }
+
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(async move {
+        match wait_for_signal().await {
+            Ok(signal) => {
+                info!("Received {}, shutting down", signal);
+                shutdown.shutdown();
+            }
+            Err(e) => error!("Not able to listen for shutdown signals -- {}", e),
+        }
+    });
     server.run().await?;
 
     Ok(())
 }
+
+/// Waits for SIGTERM or SIGINT and returns its name
+#[cfg(unix)]
+async fn wait_for_signal() -> io::Result<&'static str> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => Ok("SIGTERM"),
+        _ = interrupt.recv() => Ok("SIGINT"),
+    }
+}
+
+/// Waits for Ctrl-C and returns its name
+#[cfg(not(unix))]
+async fn wait_for_signal() -> io::Result<&'static str> {
+    tokio::signal::ctrl_c().await?;
+    Ok("Ctrl-C")
+}
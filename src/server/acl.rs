@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Matches all principals in [AclRule::principal], including anonymous clients
+const ANY_PRINCIPAL: &str = "*";
+/// Marks a topic prefix in [AclRule::topics]
+const PREFIX_WILDCARD: char = '*';
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Access control lists: which principals may publish to, subscribe to and manage topics
+///
+/// ```TOML
+/// [acl]
+/// default = "deny"
+///
+/// [[acl.rules]]
+/// principal = "billing-service"
+/// topics = ["billing.*", "invoices"]
+/// permissions = ["publish", "subscribe"]
+///
+/// [[acl.rules]]
+/// principal = "*"
+/// topics = ["public.*"]
+/// permissions = ["subscribe"]
+/// ```
+///
+/// - `default` - what is allowed without a granting rule, see [AclDefault]
+/// - `rules` - [AclRules](AclRule) granting permissions
+pub struct AclConfig {
+    pub default: AclDefault,
+    pub rules: Vec<AclRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+/// What is allowed without a rule granting it. [Permission::Admin] is never allowed without a rule
+///
+/// In serialized format it is `"allow"` or `"deny"`
+pub enum AclDefault {
+    /// Topics not listed in any rule are open to everyone, listed topics are available only as granted
+    #[default]
+    Allow,
+    /// Only what is granted by the rules is allowed
+    Deny,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+/// Rule granting `permissions` on `topics` to `principal`
+///
+/// - `principal` - the authenticated name of clients, `"*"` for all clients, including anonymous ones
+/// - `topics` - topic names, a name ending with `*` is a prefix (`"billing.*"` matches `"billing.eu"`).
+///   Not used by [Permission::Admin]
+/// - `permissions` - granted [Permissions](Permission)
+pub struct AclRule {
+    pub principal: String,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+/// What a client may do
+///
+/// In serialized format it is `"publish"`, `"subscribe"` or `"admin"`
+pub enum Permission {
+    /// Publish messages to the topic
+    Publish,
+    /// Subscribe to the topic
+    Subscribe,
+    /// Manage topics and clients of the `Server` as an [Admin](crate::server::client::Admin)
+    Admin,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::Publish => write!(f, "publish"),
+            Permission::Subscribe => write!(f, "subscribe"),
+            Permission::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl AclRule {
+    fn applies_to(&self, principal: Option<&str>) -> bool {
+        self.principal == ANY_PRINCIPAL || principal == Some(self.principal.as_str())
+    }
+
+    /// Whether the rule concerns `topic` (or managing the `Server` without a topic)
+    fn covers(&self, topic: Option<&str>) -> bool {
+        match topic {
+            Some(topic) => {
+                self.topics
+                    .iter()
+                    .any(|pattern| match pattern.strip_suffix(PREFIX_WILDCARD) {
+                        Some(prefix) => topic.starts_with(prefix),
+                        None => pattern == topic,
+                    })
+            }
+            None => self.permissions.contains(&Permission::Admin),
+        }
+    }
+}
+
+impl AclConfig {
+    /// Whether the client with `principal` (`None` for anonymous clients) has `permission` on `topic`.
+    /// [Permission::Admin] is checked without a topic and is allowed only by a rule granting it
+    pub fn is_allowed(
+        &self,
+        principal: Option<&str>,
+        permission: Permission,
+        topic: Option<&str>,
+    ) -> bool {
+        let mut covered = false;
+        for rule in self.rules.iter().filter(|rule| rule.covers(topic)) {
+            if rule.applies_to(principal) && rule.permissions.contains(&permission) {
+                return true;
+            }
+            covered = true;
+        }
+        !covered && self.default == AclDefault::Allow && permission != Permission::Admin
+    }
+
+    /// Checks that all values can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.principal.is_empty() {
+                return Err(format!("acl: principal of rule {} is empty", i));
+            }
+            if rule.permissions.is_empty() {
+                return Err(format!("acl: rule {} grants no permissions", i));
+            }
+            let topic_permissions = rule
+                .permissions
+                .iter()
+                .any(|permission| *permission != Permission::Admin);
+            if topic_permissions && rule.topics.is_empty() {
+                return Err(format!("acl: rule {} has no topics", i));
+            }
+            if let Some(pattern) = rule.topics.iter().find(|pattern| {
+                pattern.is_empty()
+                    || pattern
+                        .trim_end_matches(PREFIX_WILDCARD)
+                        .contains(PREFIX_WILDCARD)
+            }) {
+                return Err(format!(
+                    r#"acl: topic "{}" of rule {} is not a name or a prefix"#,
+                    pattern, i
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Description of `action` denied to the client with `principal` (`None` for anonymous clients)
+pub(crate) fn denied_action(principal: Option<&str>, action: &str) -> String {
+    match principal {
+        Some(principal) => format!(r#"principal "{}" cannot {}"#, principal, action),
+        None => format!("anonymous client cannot {}", action),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(default: &str) -> AclConfig {
+        toml::from_str(&format!(
+            r#"
+            default = "{}"
+
+            [[rules]]
+            principal = "billing"
+            topics = ["billing.*", "invoices"]
+            permissions = ["publish", "subscribe"]
+
+            [[rules]]
+            principal = "*"
+            topics = ["invoices"]
+            permissions = ["subscribe"]
+
+            [[rules]]
+            principal = "ops"
+            permissions = ["admin"]
+            "#,
+            default
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        use Permission::*;
+
+        for default in ["allow", "deny"] {
+            let acl = config(default);
+            assert!(acl.validate().is_ok());
+            assert!(acl.is_allowed(Some("billing"), Publish, Some("billing.eu")));
+            assert!(acl.is_allowed(Some("billing"), Subscribe, Some("invoices")));
+            assert!(!acl.is_allowed(Some("billing"), Admin, None));
+            assert!(!acl.is_allowed(Some("sales"), Publish, Some("billing.eu")));
+            assert!(!acl.is_allowed(None, Subscribe, Some("billing.eu")));
+            assert!(acl.is_allowed(Some("sales"), Subscribe, Some("invoices")));
+            assert!(acl.is_allowed(None, Subscribe, Some("invoices")));
+            assert!(!acl.is_allowed(Some("sales"), Publish, Some("invoices")));
+            assert!(acl.is_allowed(Some("ops"), Admin, None));
+        }
+
+        // topics without rules
+        assert!(config("allow").is_allowed(Some("sales"), Publish, Some("sales")));
+        assert!(config("allow").is_allowed(None, Subscribe, Some("billing")));
+        assert!(!config("deny").is_allowed(Some("sales"), Publish, Some("sales")));
+        assert!(!AclConfig::default().is_allowed(None, Admin, None));
+        assert!(!AclConfig::default().is_allowed(Some("ops"), Admin, None));
+    }
+
+    #[test]
+    fn test_validate() {
+        let rule = |topics: &[&str], permissions: Vec<Permission>| AclConfig {
+            default: AclDefault::Deny,
+            rules: vec![AclRule {
+                principal: "alice".to_string(),
+                topics: topics.iter().map(|topic| topic.to_string()).collect(),
+                permissions,
+            }],
+        };
+        assert!(rule(&["a", "b*"], vec![Permission::Publish])
+            .validate()
+            .is_ok());
+        assert!(rule(&[], vec![Permission::Admin]).validate().is_ok());
+        assert!(rule(&[], vec![Permission::Publish]).validate().is_err());
+        assert!(rule(&["a"], vec![]).validate().is_err());
+        assert!(rule(&["a*b"], vec![Permission::Publish])
+            .validate()
+            .is_err());
+        assert!(rule(&[""], vec![Permission::Publish]).validate().is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::server::topic::config::TopicConfig;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+/// Messages from `Admin` clients sent after the [ConnectionMessage](crate::server::connection_message::ConnectionMessage)
+///
+/// Each message is provided in JSON format:
+///
+/// ```JSON
+/// {"method": "<admin_method>", ...}
+/// ```
+///
+/// The `Server` replies to each message with a [ServiceMessage](crate::server::service_message::ServiceMessage),
+/// [ServiceMessage::Error](crate::server::service_message::ServiceMessage::Error) if the request failed
+pub enum AdminMessage {
+    /// Lists all topics with their configs, offsets and connected clients
+    ListTopics,
+    /// Describes topic `topic`
+    DescribeTopic { topic: String },
+    /// Creates topic `topic` with `config` (or the default config)
+    CreateTopic {
+        topic: String,
+        #[serde(default)]
+        config: Option<TopicConfig>,
+    },
+    /// Deletes topic `topic` with its log. Clients connected to the topic are disconnected
+    DeleteTopic { topic: String },
+    /// Lists all clients connected to the `Server`
+    ListConnections,
+    /// Disconnects the client with connection id `id`
+    DisconnectClient { id: u64 },
+    /// Reloads the configuration of the `Server`
+    ReloadConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            serde_json::from_str::<AdminMessage>(r#"{"method": "list_topics"}"#).unwrap(),
+            AdminMessage::ListTopics
+        );
+        assert_eq!(
+            serde_json::from_str::<AdminMessage>(
+                r#"{"method": "create_topic", "topic": "Topic", "config": {"max_message_size": 1024}}"#
+            )
+            .unwrap(),
+            AdminMessage::CreateTopic {
+                topic: "Topic".to_string(),
+                config: Some(TopicConfig {
+                    max_message_size: Some(1024),
+                    ..TopicConfig::default()
+                }),
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<AdminMessage>(r#"{"method": "disconnect_client", "id": 7}"#)
+                .unwrap(),
+            AdminMessage::DisconnectClient { id: 7 }
+        );
+
+        assert_eq!(
+            serde_json::from_str::<AdminMessage>(r#"{"method": "reload_config"}"#).unwrap(),
+            AdminMessage::ReloadConfig
+        );
+
+        assert!(serde_json::from_str::<AdminMessage>(r#"{"method": "delete_topic"}"#).is_err());
+        assert!(serde_json::from_str::<AdminMessage>(r#"{"method": "commit"}"#).is_err());
+    }
+}
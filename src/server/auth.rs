@@ -0,0 +1,279 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone)]
+#[serde(untagged)]
+/// Credentials of a client, given in `auth` of the
+/// [ConnectionMessage](crate::server::connection_message::ConnectionMessage)
+///
+/// In serialized format it is `{"username": "<username>", "password": "<password>"}` or `{"token": "<token>"}`
+pub enum Credentials {
+    Password { username: String, password: String },
+    Token { token: String },
+}
+
+impl Credentials {
+    /// Who tries to authenticate, without secrets
+    pub fn identity(&self) -> String {
+        match self {
+            Credentials::Password { username, .. } => format!(r#"user "{}""#, username),
+            Credentials::Token { .. } => "token".to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // secrets are never logged
+        match self {
+            Credentials::Password { username, .. } => f
+                .debug_struct("Password")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            Credentials::Token { .. } => f.debug_struct("Token").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Verifies credentials of clients, see [Server::with_authenticator](crate::server::Server::with_authenticator)
+pub trait Authenticator: Send + Sync {
+    /// Returns the principal (the authenticated name) of the client with `credentials`,
+    /// `None` if the credentials are not accepted
+    ///
+    /// It is called outside of the async runtime, so it may block
+    fn authenticate(&self, credentials: &Credentials) -> Option<String>;
+}
+
+/// Authenticates users by passwords. Passwords are stored as argon2 hashes in the PHC string format
+pub struct PasswordAuthenticator {
+    users: HashMap<String, String>,
+}
+
+impl PasswordAuthenticator {
+    /// Creates `PasswordAuthenticator` for `users`: usernames and their password hashes
+    ///
+    /// # Errors
+    /// If a hash is not in the PHC string format, function will return [ErrorKind::InvalidData]
+    pub fn new(users: HashMap<String, String>) -> io::Result<Self> {
+        for (username, hash) in &users {
+            PasswordHash::new(hash).map_err(|e| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(r#"password hash of user "{}": {}"#, username, e),
+                )
+            })?;
+        }
+        Ok(Self { users })
+    }
+
+    /// Loads users from TOML file `path` with a password hash for each username:
+    ///
+    /// ```TOML
+    /// alice = "$argon2id$v=19$m=19456,t=2,p=1$..."
+    /// ```
+    ///
+    /// # Errors
+    /// - All errors are inherited from [fs::read_to_string]
+    /// - If the file is not valid, function will return [ErrorKind::InvalidData]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let users = fs::read_to_string(path)?;
+        let users =
+            toml::from_str(&users).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        Self::new(users)
+    }
+}
+
+impl Authenticator for PasswordAuthenticator {
+    fn authenticate(&self, credentials: &Credentials) -> Option<String> {
+        let Credentials::Password { username, password } = credentials else {
+            return None;
+        };
+        let hash = PasswordHash::new(self.users.get(username)?).ok()?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .ok()
+            .map(|_| username.clone())
+    }
+}
+
+/// Hashes `password` for the users file of [PasswordAuthenticator]
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("default argon2 parameters are valid")
+        .to_string()
+}
+
+/// Authenticates clients by static bearer tokens
+pub struct TokenAuthenticator {
+    tokens: BTreeMap<String, String>,
+}
+
+impl TokenAuthenticator {
+    /// Creates `TokenAuthenticator` for `tokens`: principals and their tokens
+    pub fn new(tokens: BTreeMap<String, String>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl Authenticator for TokenAuthenticator {
+    fn authenticate(&self, credentials: &Credentials) -> Option<String> {
+        let Credentials::Token { token } = credentials else {
+            return None;
+        };
+        self.tokens
+            .iter()
+            .find(|(_, expected)| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+            .map(|(principal, _)| principal.clone())
+    }
+}
+
+/// Compares secrets in time independent of the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Serialize, Deserialize, Default, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Authentication of clients. Without users and tokens clients are not authenticated
+///
+/// ```TOML
+/// [auth]
+/// users_file = "./users.toml"
+/// allow_anonymous = false
+///
+/// [auth.tokens]
+/// ci = "2c7b0d6e4f..."
+/// ```
+///
+/// - `users_file` - users authenticated by [PasswordAuthenticator], see [PasswordAuthenticator::load]
+/// - `tokens` - principals and their static bearer tokens, authenticated by [TokenAuthenticator]
+/// - `allow_anonymous` - clients without credentials are accepted, `false` by default
+pub struct AuthConfig {
+    pub users_file: Option<PathBuf>,
+    pub tokens: BTreeMap<String, String>,
+    pub allow_anonymous: bool,
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // tokens are never logged
+        f.debug_struct("AuthConfig")
+            .field("users_file", &self.users_file)
+            .field("tokens", &self.tokens.keys().collect::<Vec<_>>())
+            .field("allow_anonymous", &self.allow_anonymous)
+            .finish()
+    }
+}
+
+impl AuthConfig {
+    /// Checks that all values can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        match self.tokens.iter().find(|(_, token)| token.is_empty()) {
+            Some((principal, _)) => Err(format!(
+                r#"auth: token of principal "{}" is empty"#,
+                principal
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Authenticators of users and tokens of the config
+    ///
+    /// # Errors
+    /// All errors are inherited from [PasswordAuthenticator::load]
+    pub fn authenticators(&self) -> io::Result<Vec<Arc<dyn Authenticator>>> {
+        let mut authenticators: Vec<Arc<dyn Authenticator>> = vec![];
+        if let Some(users_file) = &self.users_file {
+            let users = PasswordAuthenticator::load(users_file).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("users file {} -- {}", users_file.display(), e),
+                )
+            })?;
+            authenticators.push(Arc::new(users));
+        }
+        if !self.tokens.is_empty() {
+            authenticators.push(Arc::new(TokenAuthenticator::new(self.tokens.clone())));
+        }
+        Ok(authenticators)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            serde_json::from_str::<Credentials>(r#"{"username": "alice", "password": "secret"}"#)
+                .unwrap(),
+            Credentials::Password {
+                username: "alice".to_string(),
+                password: "secret".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Credentials>(r#"{"token": "abc"}"#).unwrap(),
+            Credentials::Token {
+                token: "abc".to_string()
+            }
+        );
+        assert!(serde_json::from_str::<Credentials>(r#"{"username": "alice"}"#).is_err());
+        assert!(!format!(
+            "{:?}",
+            Credentials::Token {
+                token: "abc".to_string()
+            }
+        )
+        .contains("abc"));
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let users = PasswordAuthenticator::new(HashMap::from([(
+            "alice".to_string(),
+            hash_password("secret"),
+        )]))
+        .unwrap();
+        let password = |username: &str, password: &str| Credentials::Password {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        assert_eq!(
+            users.authenticate(&password("alice", "secret")),
+            Some("alice".to_string())
+        );
+        assert_eq!(users.authenticate(&password("alice", "wrong")), None);
+        assert_eq!(users.authenticate(&password("bob", "secret")), None);
+        assert!(PasswordAuthenticator::new(HashMap::from([(
+            "alice".to_string(),
+            "secret".to_string()
+        )]))
+        .is_err());
+
+        let tokens =
+            TokenAuthenticator::new(BTreeMap::from([("ci".to_string(), "abc".to_string())]));
+        let token = |token: &str| Credentials::Token {
+            token: token.to_string(),
+        };
+        assert_eq!(tokens.authenticate(&token("abc")), Some("ci".to_string()));
+        assert_eq!(tokens.authenticate(&token("abd")), None);
+        assert_eq!(tokens.authenticate(&token("ab")), None);
+        assert_eq!(tokens.authenticate(&password("ci", "abc")), None);
+    }
+}
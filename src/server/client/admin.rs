@@ -0,0 +1,194 @@
+use crate::server::admin_message::AdminMessage;
+use crate::server::client::registry::{DisconnectSignal, Registration};
+use crate::server::client::server_broker::ServerBroker;
+use crate::server::framing::Framing;
+use crate::server::service_message::{DisconnectReason, ErrorCode, ServiceMessage};
+use crate::server::stream::{write_frame, ClientAddr};
+use log::{debug, info};
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// An `Admin` is one of the `Client` types being served
+///
+/// `Admin` keeps it in itself `connection_id` (server-unique id of the connection), `name` (unique name, if any),
+/// `socket_addr` (ip/port or process credentials of connected `Client`, see [ClientAddr]) and `framing` (how messages are separated in the connection)
+///
+/// The `Admin` client manages topics and connected clients of the `Server` with [AdminMessages](AdminMessage)
+pub struct Admin {
+    pub connection_id: u64,
+    pub name: Option<String>,
+    pub socket_addr: ClientAddr,
+    pub framing: Framing,
+    max_frame_size: usize,
+    registration: Registration,
+    disconnect: DisconnectSignal,
+}
+
+/// Why a request of the `Admin` failed
+#[derive(Debug)]
+pub(crate) enum AdminError {
+    /// There is no such topic
+    UnknownTopic(String),
+    /// The created topic already exists
+    TopicExists(String),
+    /// The name of the created topic does not match the topic name pattern of the `Server`
+    InvalidTopicName(String),
+    /// The config of the created topic is not valid
+    InvalidConfig(String),
+    /// There is no connected client with the id
+    UnknownClient(u64),
+    /// The topic log cannot be created or removed
+    StorageError(io::Error),
+    /// The configuration cannot be reloaded
+    ReloadFailed(io::Error),
+}
+
+impl Admin {
+    pub(crate) fn new(registration: Registration, framing: Framing, max_frame_size: usize) -> Self {
+        Self {
+            connection_id: registration.connection_id,
+            name: registration.name.clone(),
+            socket_addr: registration.socket_addr,
+            framing,
+            max_frame_size,
+            disconnect: registration.disconnect_signal(),
+            registration,
+        }
+    }
+
+    /// Start listening [AdminMessages](AdminMessage) from `Admin` client and replying to them
+    ///
+    /// Messages are read and replies are sent in the negotiated [Framing]
+    ///
+    /// # Errors
+    /// - If the connection is closed in the middle of a message, function will return [ErrorKind::InvalidData]
+    /// - If server cannot read data from client (disconnection with error, etc.),
+    ///   function will return error from reading the stream
+    /// - If reply cannot be sent, function will return error from writing the stream
+    pub(crate) async fn listen<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        buf_reader: &mut BufReader<S>,
+        server_broker: &ServerBroker,
+    ) -> io::Result<()> {
+        let mut buffer: Vec<u8> = vec![];
+        let framing = self.framing;
+        loop {
+            let read = tokio::select! {
+                read = framing.read_frame(buf_reader, &mut buffer, self.max_frame_size) => read,
+                reason = self.disconnect.wait() => {
+                    let message = ServiceMessage::Disconnected { reason };
+                    let _ = write_frame(buf_reader.get_mut(), &message.to_frame(framing)).await;
+                    break Ok(());
+                }
+            };
+            let message = match read {
+                Ok(Some(message)) => message,
+                Ok(None) => break Ok(()), // admin disconnection
+                Err(e) if e.kind() == ErrorKind::ConnectionAborted => break Ok(()), // admin disconnection
+                Err(e) => break Err(e),
+            };
+
+            let reply = self.handle_message(&message, server_broker).await;
+            write_frame(buf_reader.get_mut(), &reply.to_frame(framing)).await?;
+        }
+    }
+
+    async fn handle_message(&self, message: &[u8], server_broker: &ServerBroker) -> ServiceMessage {
+        let message = String::from_utf8_lossy(message);
+        let admin_message = match serde_json::from_str::<AdminMessage>(&message) {
+            Ok(admin_message) => admin_message,
+            Err(e) => {
+                debug!(
+                    r#"Unexpected message from admin {}: "{}""#,
+                    self.registration,
+                    message.trim_end()
+                );
+                return ServiceMessage::Error {
+                    code: ErrorCode::UnexpectedMessage,
+                    message: e.to_string(),
+                };
+            }
+        };
+
+        let result = match admin_message {
+            AdminMessage::ListTopics => Ok(ServiceMessage::Topics {
+                topics: server_broker.describe_topics().await,
+            }),
+            AdminMessage::DescribeTopic { topic } => server_broker
+                .describe_topic(&topic)
+                .await
+                .map(ServiceMessage::Topic),
+            AdminMessage::CreateTopic { topic, config } => {
+                let description = server_broker.create_topic(&topic, config).await;
+                if description.is_ok() {
+                    info!(r#"Admin {} created topic "{}""#, self.registration, topic);
+                }
+                description.map(ServiceMessage::Topic)
+            }
+            AdminMessage::DeleteTopic { topic } => {
+                let deleted = server_broker.delete_topic(&topic).await;
+                if deleted.is_ok() {
+                    info!(r#"Admin {} deleted topic "{}""#, self.registration, topic);
+                }
+                deleted.map(|_| ServiceMessage::TopicDeleted { topic })
+            }
+            AdminMessage::ListConnections => Ok(ServiceMessage::Connections {
+                connections: server_broker.connections(),
+            }),
+            AdminMessage::DisconnectClient { id } => {
+                match server_broker.disconnect_client(id, DisconnectReason::Admin) {
+                    true => {
+                        info!("Admin {} disconnected client #{}", self.registration, id);
+                        Ok(ServiceMessage::ClientDisconnected { id })
+                    }
+                    false => Err(AdminError::UnknownClient(id)),
+                }
+            }
+            AdminMessage::ReloadConfig => {
+                info!("Admin {} reloads configuration", self.registration);
+                server_broker
+                    .reload()
+                    .await
+                    .map(ServiceMessage::ConfigReloaded)
+                    .map_err(AdminError::ReloadFailed)
+            }
+        };
+        result.unwrap_or_else(|e| e.to_service_message())
+    }
+}
+
+impl fmt::Display for Admin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.registration.fmt(f)
+    }
+}
+
+impl AdminError {
+    /// [ServiceMessage::Error] sent to the admin in reply to the failed request
+    pub fn to_service_message(&self) -> ServiceMessage {
+        let (code, message) = match self {
+            AdminError::UnknownTopic(topic) => (
+                ErrorCode::UnknownTopic,
+                format!(r#"there is no topic "{}""#, topic),
+            ),
+            AdminError::TopicExists(topic) => (
+                ErrorCode::TopicExists,
+                format!(r#"topic "{}" already exists"#, topic),
+            ),
+            AdminError::InvalidTopicName(topic) => (
+                ErrorCode::InvalidTopicName,
+                format!(r#"topic name "{}" is not allowed"#, topic),
+            ),
+            AdminError::InvalidConfig(e) => (ErrorCode::InvalidConfig, e.clone()),
+            AdminError::UnknownClient(id) => (
+                ErrorCode::UnknownClient,
+                format!("there is no client #{}", id),
+            ),
+            AdminError::StorageError(e) => (ErrorCode::StorageError, e.to_string()),
+            AdminError::ReloadFailed(e) => (ErrorCode::ReloadFailed, e.to_string()),
+        };
+        ServiceMessage::Error { code, message }
+    }
+}
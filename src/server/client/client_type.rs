@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
 /// The type of [Client](crate::server::client::Client) that is served by the `Server`
 ///
-/// In serialized format is it `"subscriber"` (for `Subscriber`s) or `"publish"` (for `Publisher`s)
+/// In serialized format is it `"subscriber"` (for `Subscriber`s), `"publish"` (for `Publisher`s)
+/// or `"admin"` (for `Admin`s)
 pub enum ClientType {
     #[serde(rename = "subscribe")]
     Subscriber,
     #[serde(rename = "publish")]
     Publisher,
+    #[serde(rename = "admin")]
+    Admin,
 }
@@ -1,8 +1,11 @@
 pub use crate::server::client::client_type::ClientType;
-pub use crate::server::client::{publisher::Publisher, subscriber::Subscriber};
+pub use crate::server::client::{admin::Admin, publisher::Publisher, subscriber::Subscriber};
+use crate::server::service_message::{ErrorCode, ServiceMessage};
 
+pub mod admin;
 pub mod client_type;
 pub mod publisher;
+pub(crate) mod registry;
 pub mod server_broker;
 pub mod subscriber;
 
@@ -10,21 +13,78 @@ pub mod subscriber;
 /// While sending a Connection Message, an error may occur.
 /// They are described in this structure
 pub enum ClientConnectionError {
-    /// The sent message cannot be deserialized or it is not in the [provided](crate::server::connection_message::ConnectionMessage) format
-    UnexpectedMessage(Vec<u8>),
+    /// The sent message cannot be deserialized or it is not in the [provided](crate::server::connection_message::ConnectionMessage) format.
+    /// Contains what is wrong with the message, the message itself may contain credentials
+    UnexpectedMessage(String),
     /// Connection aborted
     Aborted,
+    /// The connection message is not received in time
+    TimedOut,
     /// Some reader error
     ReaderError,
+    /// The log of the requested topic cannot be opened
+    StorageError(std::io::Error),
+    /// There is no such topic and topics cannot be created by clients
+    UnknownTopic(String),
+    /// The name of the new topic does not match the topic name pattern of the `Server`
+    InvalidTopicName(String),
+    /// Another connected client already has the requested name
+    DuplicateName(String),
+    /// The credentials of the client are missing or not accepted. Contains who tried to authenticate
+    AuthenticationFailed(String),
+    /// The client is not allowed to connect to a topic or as an admin. Contains the denied action
+    AccessDenied(String),
+}
+
+impl ClientConnectionError {
+    /// [ServiceMessage::Error] sent to the client before closing the connection.
+    /// Returns `None` if the client cannot receive it
+    pub fn to_service_message(&self) -> Option<ServiceMessage> {
+        let (code, message) = match self {
+            ClientConnectionError::UnexpectedMessage(_) => (
+                ErrorCode::UnexpectedMessage,
+                "connection message is not in the expected format".to_string(),
+            ),
+            ClientConnectionError::Aborted
+            | ClientConnectionError::TimedOut
+            | ClientConnectionError::ReaderError => return None,
+            ClientConnectionError::StorageError(_) => (
+                ErrorCode::StorageError,
+                "topic log cannot be opened".to_string(),
+            ),
+            ClientConnectionError::UnknownTopic(topic) => (
+                ErrorCode::UnknownTopic,
+                format!(r#"there is no topic "{}""#, topic),
+            ),
+            ClientConnectionError::InvalidTopicName(topic) => (
+                ErrorCode::InvalidTopicName,
+                format!(r#"topic name "{}" is not allowed"#, topic),
+            ),
+            ClientConnectionError::DuplicateName(name) => (
+                ErrorCode::DuplicateName,
+                format!(r#"client name "{}" is already taken"#, name),
+            ),
+            ClientConnectionError::AuthenticationFailed(_) => (
+                ErrorCode::AuthenticationFailed,
+                "authentication failed".to_string(),
+            ),
+            ClientConnectionError::AccessDenied(action) => (
+                ErrorCode::AccessDenied,
+                format!("access denied: {}", action),
+            ),
+        };
+        Some(ServiceMessage::Error { code, message })
+    }
 }
 
 /// `Client` is `Server`'s client
 ///
-/// Can be [Subscriber](Subscriber) or [Publisher](Publisher)
+/// Can be [Subscriber](Subscriber), [Publisher](Publisher) or [Admin](Admin)
 ///
 /// To introduce yourself to the server and state which `ClientType` the user is and which topic he wants to connect to,
 /// he must send to server a [ConnectionMessage](crate::server::connection_message::ConnectionMessage)
 pub enum Client {
     Subscriber(Subscriber),
     Publisher(Publisher),
+    Admin(Admin),
 }
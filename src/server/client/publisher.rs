@@ -1,72 +1,239 @@
-use log::debug;
+use crate::server::client::registry::{DisconnectSignal, Registration};
+use crate::server::connection_message::{AckMode, MessageFormat};
+use crate::server::framing::Framing;
+use crate::server::publisher_message::PublisherMessage;
+use crate::server::service_message::{Ack, AckResult, PublishError, ServiceMessage};
+use crate::server::stream::{write_frame, ClientAddr};
+use crate::server::topic::{RecordMetadata, Topic};
+use log::{debug, warn};
+use std::fmt;
 use std::io;
-use std::io::{Error, ErrorKind};
-use std::net::SocketAddr;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::sync::broadcast::Sender;
+use std::io::ErrorKind;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
 /// A `Publisher` is one of the `Client` types being served
 ///
-/// `Publisher` keeps it in itself `socket_addr` (ip/port of connected `Client`) and `topic` (connected `Topic` name)
+/// `Publisher` keeps it in itself `connection_id` (server-unique id of the connection), `name` (unique name, if any),
+/// `socket_addr` (ip/port or process credentials of connected `Client`, see [ClientAddr]), `topics` (connected `Topic` names),
+/// `framing` (how messages are separated in the connection), `format` (format of published messages)
+/// and `ack` (how published messages are acknowledged)
 ///
 /// The `Publisher` client listens to messages sent by client and sends them to
-/// [Subscribers](crate::server::client::subscriber::Subscriber) that are connected to the same topic as the `Publisher`
+/// [Subscribers](crate::server::client::subscriber::Subscriber) of the topic. Messages are sent to the first topic
+/// unless another connected topic is chosen in the [PublisherMessage]
 pub struct Publisher {
-    pub socket_addr: SocketAddr,
-    pub topic: String,
-    sender: Sender<Vec<u8>>,
+    pub connection_id: u64,
+    pub name: Option<String>,
+    pub socket_addr: ClientAddr,
+    pub topics: Vec<String>,
+    pub framing: Framing,
+    pub format: MessageFormat,
+    pub ack: AckMode,
+    targets: Vec<Topic>,
+    max_frame_size: usize,
+    registration: Registration,
+    disconnect: DisconnectSignal,
 }
 
 impl Publisher {
-    pub fn new(socket_addr: SocketAddr, topic: String, sender: Sender<Vec<u8>>) -> Self {
+    pub(crate) fn new(
+        registration: Registration,
+        targets: Vec<Topic>,
+        framing: Framing,
+        format: MessageFormat,
+        ack: AckMode,
+        max_frame_size: usize,
+    ) -> Self {
         Self {
-            socket_addr,
-            topic,
-            sender,
+            connection_id: registration.connection_id,
+            name: registration.name.clone(),
+            socket_addr: registration.socket_addr,
+            topics: targets.iter().map(|target| target.name.clone()).collect(),
+            framing,
+            format,
+            ack,
+            targets,
+            max_frame_size,
+            disconnect: registration.disconnect_signal(),
+            registration,
         }
     }
 
     /// Start listening messages from `Publisher` client and sending it to connected
     /// [Subscribers](crate::server::client::subscriber::Subscriber)
     ///
+    /// A message is accepted only after it has been appended to the topic log.
+    /// Messages are read in the negotiated [Framing].
+    /// Messages bigger than `max_message_size` of the topic config are rejected.
+    /// With [MessageFormat::Json] only [PublisherMessages](PublisherMessage) are accepted
+    ///
+    /// Results of publishing are sent back to the client according to the negotiated [AckMode].
+    /// Clients with presence events are notified about other clients of their topics.
+    /// A publisher waiting for slow subscribers of a topic with
+    /// [SlowConsumerPolicy::Block](crate::server::topic::config::SlowConsumerPolicy::Block) can still be disconnected by the `Server`
+    ///
+    /// The client connection read through `buf_reader` can be any stream, as for [Subscriber::listen](crate::server::client::Subscriber::listen)
+    ///
     /// # Errors
-    /// - If somehow read data is has invalid ending, function will return [ErrorKind::InvalidData]
+    /// - If the connection is closed in the middle of a message, function will return [ErrorKind::InvalidData]
     /// - If server cannot read data from client (disconnection with error, etc.),
-    /// function will return error from [TcpStream::read]
-    /// - If publisher-subscriber channel will aborted, function will return [ErrorKind::BrokenPipe]
-    pub async fn listen(&mut self, buf_reader: &mut BufReader<&mut TcpStream>) -> io::Result<()> {
+    ///   function will return error from reading the stream
+    /// - If the message cannot be appended to the topic log, function will return error from [Topic::publish]
+    /// - If acknowledgement or presence event cannot be sent, function will return error from writing the stream
+    pub async fn listen<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        buf_reader: &mut BufReader<S>,
+    ) -> io::Result<()> {
+        let mut buffer: Vec<u8> = vec![];
+        let mut acks: Vec<Ack> = vec![];
+        let mut sequence = 0u64;
+        let framing = self.framing;
         loop {
-            let mut buffer: Vec<u8> = vec![];
-            let sz = match buf_reader
-                .read_until(crate::server::client::server_broker::DELIMITER, &mut buffer)
-                .await
-            {
-                Ok(0) => break Ok(()), // publisher disconnection
-                Ok(sz) => sz,
+            let read = tokio::select! {
+                read = framing.read_frame(buf_reader, &mut buffer, self.max_frame_size) => read,
+                event = self.registration.next_event() => {
+                    let stream = buf_reader.get_mut();
+                    write_frame(stream, &event.to_frame(framing)).await?;
+                    continue;
+                }
+                reason = self.disconnect.wait() => {
+                    let message = ServiceMessage::Disconnected { reason };
+                    let _ = write_frame(buf_reader.get_mut(), &message.to_frame(framing)).await;
+                    break Ok(());
+                }
+            };
+            let message = match read {
+                Ok(Some(message)) => message,
+                Ok(None) => break Ok(()), // publisher disconnection
                 Err(e) if e.kind() == ErrorKind::ConnectionAborted => break Ok(()), // publisher disconnection
-                Err(e) => break Err(Error::from(e)),
+                Err(e) => break Err(e),
             };
 
             debug!(
-                "Received message from publisher {}: \"{}\"",
-                self.socket_addr,
-                String::from_utf8_lossy(buffer.as_slice())
-                    .strip_suffix("\n")
-                    .unwrap_or("")
+                "Received message of {} bytes from publisher {}: \"{}\"",
+                message.len(),
+                self.registration,
+                String::from_utf8_lossy(message.as_slice())
             );
 
-            if sz == 0 {
-                break Ok(());
+            // publishing to a topic with SlowConsumerPolicy::Block waits for slow subscribers
+            let mut disconnect = self.disconnect.clone();
+            let published = tokio::select! {
+                published = self.publish(message) => published,
+                reason = disconnect.wait() => {
+                    let message = ServiceMessage::Disconnected { reason };
+                    let _ = write_frame(buf_reader.get_mut(), &message.to_frame(framing)).await;
+                    break Ok(());
+                }
+            };
+            let result = match &published {
+                Ok(offset) => AckResult::Offset(*offset),
+                Err(Rejection::Invalid(error)) => AckResult::Error(*error),
+                Err(Rejection::Failed(_)) => AckResult::Error(PublishError::StorageError),
+            };
+            let ack = Ack { sequence, result };
+            sequence += 1;
+
+            let reply = match self.ack {
+                AckMode::None => None,
+                AckMode::Message => Some(ServiceMessage::Ack(ack)),
+                AckMode::Batch => {
+                    acks.push(ack);
+                    // messages already received from the client are acknowledged together
+                    match buf_reader.buffer().is_empty()
+                        || matches!(published, Err(Rejection::Failed(_)))
+                    {
+                        true => Some(ServiceMessage::BatchAck {
+                            acks: std::mem::take(&mut acks),
+                        }),
+                        false => None,
+                    }
+                }
+            };
+            if let Some(reply) = reply {
+                let stream = buf_reader.get_mut();
+                if let Err(e) = write_frame(stream, &reply.to_frame(self.framing)).await {
+                    break Err(e);
+                }
             }
-            if buffer[sz - 1] != crate::server::client::server_broker::DELIMITER {
-                break Err(Error::from(ErrorKind::InvalidData));
+
+            if let Err(Rejection::Failed(e)) = published {
+                break Err(e);
             }
+        }
+    }
+
+    /// Appends the message to the log of the target topic and returns its offset
+    async fn publish(&self, message: Vec<u8>) -> Result<u64, Rejection> {
+        let size = message.len();
+        let mut metadata = RecordMetadata::new(self.connection_id, self.name.clone());
+        let (topic, message) = match self.format {
+            MessageFormat::Raw => (None, message),
+            MessageFormat::Json => match serde_json::from_slice::<PublisherMessage>(&message) {
+                Ok(PublisherMessage::SendMessage {
+                    text,
+                    topic,
+                    key,
+                    headers,
+                }) => {
+                    metadata.key = key;
+                    metadata.headers = headers;
+                    (topic, text.into_bytes())
+                }
+                Err(e) => {
+                    warn!(
+                        "Rejected message from publisher {}: {}",
+                        self.registration, e
+                    );
+                    return Err(Rejection::Invalid(PublishError::InvalidMessage));
+                }
+            },
+        };
+
+        let target = match &topic {
+            Some(topic) => match self.targets.iter().find(|target| target.name == *topic) {
+                Some(target) => target,
+                None => {
+                    warn!(
+                        r#"Rejected message from publisher {}: not connected to topic "{}""#,
+                        self.registration, topic
+                    );
+                    return Err(Rejection::Invalid(PublishError::UnknownTopic));
+                }
+            },
+            None => &self.targets[0],
+        };
 
-            match self.sender.send(buffer) {
-                Ok(_) => {}
-                Err(_) => break Err(Error::from(ErrorKind::BrokenPipe)),
+        if let Some(max_message_size) = target.config().max_message_size {
+            if size > max_message_size {
+                warn!(
+                    r#"Rejected message of {} bytes from publisher {}: topic "{}" accepts messages up to {} bytes"#,
+                    size, self.registration, target.name, max_message_size
+                );
+                return Err(Rejection::Invalid(PublishError::MessageTooLarge));
             }
         }
+
+        let payload_size = message.len();
+        let offset = target
+            .publish(metadata, message)
+            .await
+            .map_err(Rejection::Failed)?;
+        target.metrics.published(payload_size);
+        Ok(offset)
     }
 }
+
+impl fmt::Display for Publisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.registration.fmt(f)
+    }
+}
+
+/// Why a message of the `Publisher` is not published
+enum Rejection {
+    /// The message is rejected, the publisher can continue
+    Invalid(PublishError),
+    /// The topic log failed, the publisher is disconnected
+    Failed(io::Error),
+}
@@ -0,0 +1,517 @@
+use crate::server::client::{ClientConnectionError, ClientType};
+use crate::server::service_message::{
+    ClientInfo, ClientSummary, ConnectionDescription, DisconnectReason, Role, ServiceMessage,
+    TopicClients,
+};
+use crate::server::stream::ClientAddr;
+use crate::server::topic::record::unix_time;
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::pending;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Registry of the clients connected to the `Server`
+///
+/// Each connection gets a server-unique `connection_id`. Client names are unique among connected clients.
+/// Clients with presence events are notified when other clients connect to or disconnect from their topics
+pub(crate) struct ClientRegistry {
+    clients: Mutex<HashMap<u64, ClientEntry>>,
+    reserved_names: Mutex<HashSet<String>>, // names of clients being connected, see [ClientRegistry::reserve_name]
+    next_connection_id: AtomicU64,
+    events: broadcast::Sender<PresenceEvent>,
+    closed: Mutex<Option<DisconnectReason>>, // clients registered after closing are disconnected at once
+}
+
+/// A client connected to the `Server`
+struct ClientEntry {
+    name: Option<String>,
+    principal: Option<String>,
+    socket_addr: ClientAddr,
+    client_type: ClientType,
+    topics: Vec<String>,
+    disconnect: watch::Sender<Option<DisconnectReason>>,
+}
+
+/// The client connected to or disconnected from `topics`
+#[derive(Debug, Clone)]
+struct PresenceEvent {
+    connection_id: u64,
+    name: Option<String>,
+    client_type: ClientType,
+    topics: Vec<String>,
+    joined: bool,
+}
+
+/// Who the client is: its unique name and its principal (the authenticated name), if any
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ClientIdentity {
+    pub name: Option<String>,
+    pub principal: Option<String>,
+}
+
+/// Name of a client being connected, reserved by [ClientRegistry::reserve_name]. The name is released when dropped
+pub(crate) struct NameReservation {
+    registry: Arc<ClientRegistry>,
+    name: Option<String>,
+}
+
+/// Registration of a client in [ClientRegistry]. The client is removed from the registry when dropped
+pub(crate) struct Registration {
+    registry: Arc<ClientRegistry>,
+    pub connection_id: u64,
+    pub name: Option<String>,
+    pub principal: Option<String>,
+    pub socket_addr: ClientAddr,
+    topics: Vec<String>,
+    events: Option<broadcast::Receiver<PresenceEvent>>,
+    connection_info: Option<ServiceMessage>, // sent before the first event
+    disconnect: watch::Receiver<Option<DisconnectReason>>,
+}
+
+/// Completes when the client is disconnected by the `Server`, see [ClientRegistry::disconnect]
+#[derive(Clone)]
+pub(crate) struct DisconnectSignal(watch::Receiver<Option<DisconnectReason>>);
+
+impl Default for ClientRegistry {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        Self {
+            clients: Mutex::default(),
+            reserved_names: Mutex::default(),
+            next_connection_id: AtomicU64::default(),
+            events,
+            closed: Mutex::default(),
+        }
+    }
+}
+
+impl ClientRegistry {
+    /// Returns a new server-unique connection id
+    pub fn next_connection_id(&self) -> u64 {
+        self.next_connection_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Reserves `name` for a client being connected, so that no other client takes it before
+    /// the client is registered with [ClientRegistry::register]
+    ///
+    /// # Errors
+    /// [ClientConnectionError::DuplicateName] if a connected client already has the name or it is reserved
+    pub fn reserve_name(
+        self: &Arc<Self>,
+        name: Option<String>,
+    ) -> Result<NameReservation, ClientConnectionError> {
+        if let Some(name) = &name {
+            let clients = self.clients.lock().unwrap();
+            let mut reserved_names = self.reserved_names.lock().unwrap();
+            if reserved_names.contains(name)
+                || clients
+                    .values()
+                    .any(|client| client.name.as_ref() == Some(name))
+            {
+                return Err(ClientConnectionError::DuplicateName(name.clone()));
+            }
+            reserved_names.insert(name.clone());
+        }
+        Ok(NameReservation {
+            registry: self.clone(),
+            name,
+        })
+    }
+
+    /// Registers the client connected with `connection_id` to `topics`
+    /// and notifies other clients connected to these topics
+    ///
+    /// With `presence` the client receives [ServiceMessage::ConnectionInfo] and then presence events
+    /// from [Registration::next_event]
+    ///
+    /// # Errors
+    /// [ClientConnectionError::DuplicateName] if a connected client already has the name
+    pub fn register(
+        self: &Arc<Self>,
+        connection_id: u64,
+        identity: ClientIdentity,
+        socket_addr: ClientAddr,
+        client_type: ClientType,
+        topics: Vec<String>,
+        presence: bool,
+    ) -> Result<Registration, ClientConnectionError> {
+        let ClientIdentity { name, principal } = identity;
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(name) = &name {
+            if clients
+                .values()
+                .any(|client| client.name.as_ref() == Some(name))
+            {
+                return Err(ClientConnectionError::DuplicateName(name.clone()));
+            }
+        }
+
+        // the snapshot and the subscription are taken under the lock, so that no event is missed or repeated
+        let connection_info = presence.then(|| ServiceMessage::ConnectionInfo {
+            me: ClientInfo {
+                id: connection_id,
+                name: name.clone(),
+                role: client_type.role(),
+            },
+            topics: topics
+                .iter()
+                .map(|topic| (topic.clone(), Self::topic_clients(&clients, topic)))
+                .collect(),
+            time: unix_time(),
+        });
+        let events = presence.then(|| self.events.subscribe());
+
+        let (disconnect_sender, disconnect) = watch::channel(*self.closed.lock().unwrap());
+        let entry = ClientEntry {
+            name: name.clone(),
+            principal: principal.clone(),
+            socket_addr,
+            client_type,
+            topics: topics.clone(),
+            disconnect: disconnect_sender,
+        };
+        self.notify(connection_id, &entry, entry.topics.clone(), true);
+        clients.insert(connection_id, entry);
+        Ok(Registration {
+            registry: self.clone(),
+            connection_id,
+            name,
+            principal,
+            socket_addr,
+            topics,
+            events,
+            connection_info,
+            disconnect,
+        })
+    }
+
+    /// All connected clients ordered by connection id
+    pub fn connections(&self) -> Vec<ConnectionDescription> {
+        let clients = self.clients.lock().unwrap();
+        let mut connections: Vec<ConnectionDescription> = clients
+            .iter()
+            .map(|(connection_id, client)| ConnectionDescription {
+                id: *connection_id,
+                name: client.name.clone(),
+                role: client.client_type.role(),
+                address: client.socket_addr.to_string(),
+                topics: client.topics.clone(),
+                principal: client.principal.clone(),
+            })
+            .collect();
+        connections.sort_by_key(|connection| connection.id);
+        connections
+    }
+
+    /// Publishers and subscribers connected to `topic`
+    pub fn clients_of(&self, topic: &str) -> TopicClients {
+        Self::topic_clients(&self.clients.lock().unwrap(), topic)
+    }
+
+    /// Disconnects the client with `connection_id` for the `reason`.
+    /// Returns `false` if there is no such client
+    pub fn disconnect(&self, connection_id: u64, reason: DisconnectReason) -> bool {
+        match self.clients.lock().unwrap().get(&connection_id) {
+            Some(client) => {
+                client.disconnect.send_replace(Some(reason));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disconnects all clients connected to `topic` for the `reason`
+    pub fn disconnect_topic(&self, topic: &str, reason: DisconnectReason) {
+        for client in self.clients.lock().unwrap().values() {
+            if client.topics.iter().any(|name| name == topic) {
+                client.disconnect.send_replace(Some(reason));
+            }
+        }
+    }
+
+    /// Disconnects all clients, including clients registered later, for the `reason`
+    pub fn close(&self, reason: DisconnectReason) {
+        let clients = self.clients.lock().unwrap();
+        *self.closed.lock().unwrap() = Some(reason);
+        for client in clients.values() {
+            client.disconnect.send_replace(Some(reason));
+        }
+    }
+
+    /// Publishers and subscribers of `topic`, ordered by connection id
+    fn topic_clients(clients: &HashMap<u64, ClientEntry>, topic: &str) -> TopicClients {
+        let mut connected: Vec<(&u64, &ClientEntry)> = clients
+            .iter()
+            .filter(|(_, client)| client.topics.iter().any(|name| name == topic))
+            .collect();
+        connected.sort_by_key(|(connection_id, _)| **connection_id);
+        let mut topic_clients = TopicClients::default();
+        for (connection_id, client) in connected {
+            let summary = ClientSummary {
+                id: *connection_id,
+                name: client.name.clone(),
+            };
+            match client.client_type {
+                ClientType::Publisher => topic_clients.publishers.push(summary),
+                ClientType::Subscriber => topic_clients.subscribers.push(summary),
+                ClientType::Admin => {}
+            }
+        }
+        topic_clients
+    }
+
+    fn notify(&self, connection_id: u64, client: &ClientEntry, topics: Vec<String>, joined: bool) {
+        // there may be no clients with presence events
+        let _ = self.events.send(PresenceEvent {
+            connection_id,
+            name: client.name.clone(),
+            client_type: client.client_type,
+            topics,
+            joined,
+        });
+    }
+}
+
+impl Drop for NameReservation {
+    fn drop(&mut self) {
+        if let Some(name) = &self.name {
+            self.registry.reserved_names.lock().unwrap().remove(name);
+        }
+    }
+}
+
+impl Registration {
+    /// Adds `topic` to the topics of the client and notifies other clients connected to it
+    pub fn add_topic(&mut self, topic: String) {
+        let mut clients = self.registry.clients.lock().unwrap();
+        if let Some(client) = clients.get_mut(&self.connection_id) {
+            client.topics.push(topic.clone());
+            self.registry
+                .notify(self.connection_id, client, vec![topic.clone()], true);
+        }
+        self.topics.push(topic);
+    }
+
+    /// Signal of the disconnection of the client by the `Server`
+    pub fn disconnect_signal(&self) -> DisconnectSignal {
+        DisconnectSignal(self.disconnect.clone())
+    }
+
+    /// Waits for the next presence event concerning the topics of the client.
+    /// The first one is [ServiceMessage::ConnectionInfo]. Never completes for clients without presence events
+    pub async fn next_event(&mut self) -> ServiceMessage {
+        if let Some(connection_info) = self.connection_info.take() {
+            return connection_info;
+        }
+        let Some(events) = &mut self.events else {
+            return pending().await;
+        };
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(count)) => {
+                    debug!(
+                        "Client {} missed {} presence events",
+                        self.connection_id, count
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => return pending().await,
+            };
+            if event.connection_id == self.connection_id {
+                continue;
+            }
+            let topics: Vec<String> = event
+                .topics
+                .into_iter()
+                .filter(|topic| self.topics.contains(topic))
+                .collect();
+            if topics.is_empty() {
+                continue;
+            }
+            let client = ClientSummary {
+                id: event.connection_id,
+                name: event.name,
+            };
+            let time = unix_time();
+            return match (event.client_type, event.joined) {
+                (ClientType::Publisher, true) => ServiceMessage::NewPublisher {
+                    publisher: client,
+                    connected_topics: topics,
+                    time,
+                },
+                (ClientType::Subscriber, true) => ServiceMessage::NewSubscriber {
+                    subscriber: client,
+                    connected_topics: topics,
+                    time,
+                },
+                (ClientType::Publisher, false) => ServiceMessage::PublisherLeft {
+                    publisher: client,
+                    disconnected_topics: topics,
+                    time,
+                },
+                (ClientType::Subscriber, false) => ServiceMessage::SubscriberLeft {
+                    subscriber: client,
+                    disconnected_topics: topics,
+                    time,
+                },
+                (ClientType::Admin, _) => continue, // admins are not connected to topics
+            };
+        }
+    }
+}
+
+impl DisconnectSignal {
+    /// Waits until the client is disconnected by the `Server` and returns the reason
+    pub async fn wait(&mut self) -> DisconnectReason {
+        let reason = match self.0.wait_for(Option::is_some).await {
+            Ok(reason) => *reason,
+            Err(_) => None, // the client is removed from the registry
+        };
+        match reason {
+            Some(reason) => reason,
+            None => pending().await,
+        }
+    }
+}
+
+impl ClientType {
+    fn role(&self) -> Role {
+        match self {
+            ClientType::Publisher => Role::Publisher,
+            ClientType::Subscriber => Role::Subscriber,
+            ClientType::Admin => Role::Admin,
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut clients = self.registry.clients.lock().unwrap();
+        if let Some(client) = clients.remove(&self.connection_id) {
+            self.registry
+                .notify(self.connection_id, &client, client.topics.clone(), false);
+        }
+    }
+}
+
+impl fmt::Display for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(
+                f,
+                r#"#{} "{}" ({})"#,
+                self.connection_id, name, self.socket_addr
+            )?,
+            None => write!(f, "#{} ({})", self.connection_id, self.socket_addr)?,
+        }
+        match &self.principal {
+            Some(principal) => write!(f, r#" as "{}""#, principal),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    fn register(
+        registry: &Arc<ClientRegistry>,
+        name: Option<&str>,
+        client_type: ClientType,
+        topics: &[&str],
+    ) -> Result<Registration, ClientConnectionError> {
+        registry.register(
+            registry.next_connection_id(),
+            ClientIdentity {
+                name: name.map(String::from),
+                principal: None,
+            },
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 5000).into(),
+            client_type,
+            topics.iter().map(|topic| topic.to_string()).collect(),
+            true,
+        )
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let registry = Arc::new(ClientRegistry::default());
+        let first = register(&registry, Some("sensor"), ClientType::Publisher, &["a"]).unwrap();
+        let anonymous = register(&registry, None, ClientType::Publisher, &["a"]).unwrap();
+        assert!(register(&registry, None, ClientType::Publisher, &["a"]).is_ok());
+        assert_eq!(first.to_string(), r#"#1 "sensor" (127.0.0.1:5000)"#);
+        assert_eq!(anonymous.to_string(), "#2 (127.0.0.1:5000)");
+
+        assert!(matches!(
+            register(&registry, Some("sensor"), ClientType::Subscriber, &["b"]),
+            Err(ClientConnectionError::DuplicateName(_))
+        ));
+
+        drop(first);
+        let _second = register(&registry, Some("sensor"), ClientType::Subscriber, &["b"]).unwrap();
+
+        let reservation = registry.reserve_name(Some("probe".to_string())).unwrap();
+        assert!(matches!(
+            registry.reserve_name(Some("probe".to_string())),
+            Err(ClientConnectionError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            registry.reserve_name(Some("sensor".to_string())),
+            Err(ClientConnectionError::DuplicateName(_))
+        ));
+        assert!(registry.reserve_name(None).is_ok());
+        drop(reservation);
+        assert!(registry.reserve_name(Some("probe".to_string())).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_presence() {
+        let registry = Arc::new(ClientRegistry::default());
+        let _publisher =
+            register(&registry, Some("sensor"), ClientType::Publisher, &["a"]).unwrap();
+        let mut subscriber =
+            register(&registry, None, ClientType::Subscriber, &["a", "b"]).unwrap();
+        match subscriber.next_event().await {
+            ServiceMessage::ConnectionInfo { me, topics, .. } => {
+                assert_eq!(me.id, 2);
+                assert_eq!(me.role, Role::Subscriber);
+                assert_eq!(
+                    topics["a"].publishers,
+                    vec![ClientSummary {
+                        id: 1,
+                        name: Some("sensor".to_string())
+                    }]
+                );
+                assert!(topics["a"].subscribers.is_empty());
+                assert_eq!(topics["b"], TopicClients::default());
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+
+        // clients of other topics are not reported
+        let _other = register(&registry, None, ClientType::Publisher, &["c"]).unwrap();
+        let second = register(&registry, None, ClientType::Subscriber, &["b", "c"]).unwrap();
+        assert!(matches!(
+            subscriber.next_event().await,
+            ServiceMessage::NewSubscriber { subscriber: ClientSummary { id: 4, .. }, connected_topics, .. }
+                if connected_topics == vec!["b".to_string()]
+        ));
+        drop(second);
+        assert!(matches!(
+            subscriber.next_event().await,
+            ServiceMessage::SubscriberLeft {
+                subscriber: ClientSummary { id: 4, .. },
+                ..
+            }
+        ));
+    }
+}
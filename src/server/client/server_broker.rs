@@ -1,86 +1,580 @@
+use crate::server::acl::{denied_action, Permission};
+use crate::server::auth::{Authenticator, Credentials};
+use crate::server::client::admin::{Admin, AdminError};
+use crate::server::client::registry::{ClientIdentity, ClientRegistry, Registration};
+use crate::server::client::subscriber::{Subscription, TopicWatch};
 use crate::server::client::{Client, ClientConnectionError, ClientType, Publisher, Subscriber};
-use crate::server::connection_message::ConnectionMessage;
+use crate::server::config::{ConfigLoader, Limits, ServerConfig};
+use crate::server::connection_message::{ConnectionMessage, MessageFormat};
+use crate::server::metrics::{MetricsWriter, ServerMetrics};
+use crate::server::service_message::{
+    ConfigChanges, ConnectionDescription, DisconnectReason, TopicDescription,
+};
+use crate::server::stream::ClientAddr;
+use crate::server::topic::config::TopicConfig;
+use crate::server::topic::filter::TopicFilter;
 use crate::server::topic::Topic;
+use log::{error, info, warn};
+use regex::Regex;
 use std::collections::HashMap;
+use std::io;
 use std::io::ErrorKind;
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::sync::broadcast::{Receiver, Sender};
-use tokio::sync::Mutex;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::sync::{watch, Mutex};
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
 
 pub const DELIMITER: u8 = 10;
+/// How long a connected client may take to send its connection message
+pub const CONNECTION_MESSAGE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// ServerBroker is auxiliary Server object
 ///
 /// In a nutshell it allows you to conveniently create a client when connected via:
 /// - creating topics
 /// - creating communication channels between publishers and subscribers
-/// ```
+/// - joining subscribers to consumer groups
+/// - subscribing to all topics matching a [TopicFilter], including topics created later
+/// - authenticating clients and checking their access to topics
+/// - identifying connected clients by connection ids and unique names
+/// - managing topics and connected clients by admins
+/// - reloading the configuration of the running `Server`
+/// - collecting metrics of topics and connections
+///
+/// Topic logs are stored inside `data_dir` of the config. Without it topics are kept in memory.
+/// Topics are created according to the topic settings of the config
 pub(crate) struct ServerBroker {
     topics: Arc<Mutex<HashMap<String, Topic>>>,
+    topics_changed: watch::Sender<u64>, // notifies subscribers with topic filters about new topics
+    creating_topics: Mutex<()>, // topics are created and deleted one at a time, their logs are opened without locking `topics`
+    data_dir: Option<PathBuf>,
+    settings: Arc<RwLock<Settings>>, // shared with topic watches of subscribers
+    config_loader: Option<ConfigLoader>,
+    authenticators: Vec<Arc<dyn Authenticator>>, // set by the application, used together with ones of the config
+    clients: Arc<ClientRegistry>,
+    metrics: ServerMetrics,
+}
+
+/// The current config of the `Server`, replaced when the config is reloaded
+struct Settings {
+    config: ServerConfig,
+    topic_name_regex: Option<Regex>,
+    authenticators: Vec<Arc<dyn Authenticator>>,
+}
+
+impl Settings {
+    /// # Errors
+    /// - If `config` is not valid, function will return [ErrorKind::InvalidData]
+    /// - All other errors are inherited from [AuthConfig::authenticators](crate::server::auth::AuthConfig::authenticators)
+    fn new(config: ServerConfig) -> io::Result<Self> {
+        config
+            .validate()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let topic_name_regex = config
+            .topics
+            .topic_name_regex()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let authenticators = config.auth.authenticators()?;
+        Ok(Self {
+            config,
+            topic_name_regex,
+            authenticators,
+        })
+    }
 }
 
 impl ServerBroker {
-    pub fn default() -> Self {
-        Self {
-            topics: Arc::new(Mutex::new(HashMap::default())),
+    /// Creates `ServerBroker`, recovers all topics stored inside `data_dir`
+    /// and creates topics declared in the topic settings of `config`
+    ///
+    /// # Errors
+    /// - If `config` is not valid, function will return [ErrorKind::InvalidData]
+    /// - All other errors are inherited from [Topic::open] and [Topic::set_config]
+    pub async fn open(config: ServerConfig) -> io::Result<Self> {
+        let settings = Settings::new(config)?;
+        let data_dir = settings.config.data_dir.clone();
+        let topics_config = &settings.config.topics;
+        let mut topics = HashMap::default();
+        if let Some(data_dir) = &data_dir {
+            for topic_name in Topic::stored_names(data_dir)? {
+                let topic =
+                    Topic::open(topic_name.clone(), Some(data_dir), &topics_config.default)?;
+                let (start_offset, next_offset) = topic.offsets().await;
+                info!(
+                    r#"Recovered topic "{}" with messages {}..{}"#,
+                    topic_name, start_offset, next_offset
+                );
+                topics.insert(topic_name, topic);
+            }
+        }
+
+        for (topic_name, config) in &topics_config.topics {
+            match topics.get(topic_name) {
+                Some(topic) if topic.config() != *config => {
+                    topic.set_config(config.clone())?;
+                    info!(r#"Updated config of topic "{}": {:?}"#, topic_name, config);
+                }
+                Some(_) => {}
+                None => {
+                    let topic = Topic::open(topic_name.clone(), data_dir.as_deref(), config)?;
+                    info!(r#"Created topic "{}" with config {:?}"#, topic_name, config);
+                    topics.insert(topic_name.clone(), topic);
+                }
+            }
+        }
+
+        let (topics_changed, _) = watch::channel(0);
+        Ok(Self {
+            topics: Arc::new(Mutex::new(topics)),
+            topics_changed,
+            creating_topics: Mutex::default(),
+            data_dir,
+            settings: Arc::new(RwLock::new(settings)),
+            config_loader: None,
+            authenticators: vec![],
+            clients: Arc::default(),
+            metrics: ServerMetrics::default(),
+        })
+    }
+
+    /// Sets how the config is loaded by [ServerBroker::reload]
+    pub fn with_config_loader(mut self, config_loader: Option<ConfigLoader>) -> Self {
+        self.config_loader = config_loader;
+        self
+    }
+
+    /// Sets authenticators used together with the authenticators of the config
+    pub fn with_authenticators(mut self, authenticators: Vec<Arc<dyn Authenticator>>) -> Self {
+        self.authenticators = authenticators;
+        self
+    }
+
+    /// Current limits of the `Server`
+    pub fn limits(&self) -> Limits {
+        self.settings.read().unwrap().config.limits.clone()
+    }
+
+    /// How long clients are served after the shutdown is started
+    pub fn shutdown_timeout(&self) -> Duration {
+        self.settings.read().unwrap().config.shutdown_timeout
+    }
+
+    /// Returns a new server-unique connection id
+    pub fn next_connection_id(&self) -> u64 {
+        self.clients.next_connection_id()
+    }
+
+    /// Returns topic `topic`. If there is no such topic, it is created with `config` (or the default config)
+    ///
+    /// # Errors
+    /// - [ClientConnectionError::UnknownTopic] if topics cannot be created by clients
+    /// - [ClientConnectionError::InvalidTopicName] if the name does not match the topic name pattern
+    /// - [ClientConnectionError::StorageError] if the topic log cannot be opened
+    async fn get_topic(
+        &self,
+        topic: &str,
+        config: Option<&TopicConfig>,
+    ) -> Result<Topic, ClientConnectionError> {
+        if let Some(existing) = self.topics.lock().await.get(topic) {
+            return Ok(existing.clone());
+        }
+        let _creating = self.creating_topics.lock().await;
+        // the topic could be created while waiting
+        if let Some(existing) = self.topics.lock().await.get(topic) {
+            return Ok(existing.clone());
         }
+        self.check_new_topic(topic)?;
+        self.insert_topic(topic, config)
+            .await
+            .map_err(ClientConnectionError::StorageError)
     }
 
-    async fn get_topic(&self, topic: &str) -> Topic {
-        let mut topics_lock = self.topics.lock().await;
-        if !topics_lock.contains_key(topic) {
-            topics_lock.insert(String::from(topic), Topic::new(String::from(topic)).await);
+    /// Checks that topic `topic` can be created by a client
+    ///
+    /// # Errors
+    /// - [ClientConnectionError::UnknownTopic] if topics cannot be created by clients
+    /// - [ClientConnectionError::InvalidTopicName] if the name does not match the topic name pattern
+    fn check_new_topic(&self, topic: &str) -> Result<(), ClientConnectionError> {
+        if !self.settings.read().unwrap().config.topics.auto_create {
+            return Err(ClientConnectionError::UnknownTopic(String::from(topic)));
         }
-        topics_lock.get_mut(topic).unwrap().clone()
+        if !self.is_valid_name(topic) {
+            return Err(ClientConnectionError::InvalidTopicName(String::from(topic)));
+        }
+        Ok(())
+    }
+
+    fn is_valid_name(&self, topic: &str) -> bool {
+        self.settings
+            .read()
+            .unwrap()
+            .topic_name_regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(topic))
     }
 
-    async fn get_receiver(&self, topic: &str) -> Receiver<Vec<u8>> {
-        self.get_topic(topic).await.sender.subscribe()
+    /// Creates topic `topic` with `config` (or the default config) and notifies subscribers with topic filters.
+    /// The caller holds `creating_topics` and has checked that there is no such topic
+    ///
+    /// # Errors
+    /// All errors are inherited from [Topic::open]
+    async fn insert_topic(&self, topic: &str, config: Option<&TopicConfig>) -> io::Result<Topic> {
+        let config = match config {
+            Some(config) => config.clone(),
+            None => self.settings.read().unwrap().config.topics.default.clone(),
+        };
+        let (topic_name, data_dir, open_config) =
+            (String::from(topic), self.data_dir.clone(), config.clone());
+        let new_topic =
+            spawn_blocking(move || Topic::open(topic_name, data_dir.as_deref(), &open_config))
+                .await??;
+        info!(r#"Created topic "{}" with config {:?}"#, topic, config);
+        self.topics
+            .lock()
+            .await
+            .insert(String::from(topic), new_topic.clone());
+        self.topics_changed
+            .send_modify(|generation| *generation += 1);
+        Ok(new_topic)
+    }
+
+    async fn describe(&self, topic: &Topic) -> TopicDescription {
+        let (start_offset, next_offset) = topic.offsets().await;
+        TopicDescription {
+            name: topic.name.clone(),
+            config: topic.config(),
+            start_offset,
+            next_offset,
+            clients: self.clients.clients_of(&topic.name),
+        }
+    }
+
+    /// Descriptions of all topics ordered by name
+    pub async fn describe_topics(&self) -> Vec<TopicDescription> {
+        let mut topics: Vec<Topic> = self.topics.lock().await.values().cloned().collect();
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut descriptions = vec![];
+        for topic in &topics {
+            descriptions.push(self.describe(topic).await);
+        }
+        descriptions
+    }
+
+    /// Description of topic `topic`
+    ///
+    /// # Errors
+    /// [AdminError::UnknownTopic] if there is no such topic
+    pub async fn describe_topic(&self, topic: &str) -> Result<TopicDescription, AdminError> {
+        let existing = self.topics.lock().await.get(topic).cloned();
+        match existing {
+            Some(existing) => Ok(self.describe(&existing).await),
+            None => Err(AdminError::UnknownTopic(String::from(topic))),
+        }
+    }
+
+    /// Creates topic `topic` with `config` (or the default config), even if topics cannot be created by clients
+    ///
+    /// # Errors
+    /// - [AdminError::TopicExists] if there is already such topic
+    /// - [AdminError::InvalidTopicName] if the name does not match the topic name pattern
+    /// - [AdminError::InvalidConfig] if `config` is not valid
+    /// - [AdminError::StorageError] if the topic log cannot be created
+    pub async fn create_topic(
+        &self,
+        topic: &str,
+        config: Option<TopicConfig>,
+    ) -> Result<TopicDescription, AdminError> {
+        if let Some(config) = &config {
+            config.validate().map_err(AdminError::InvalidConfig)?;
+        }
+        let creating = self.creating_topics.lock().await;
+        if self.topics.lock().await.contains_key(topic) {
+            return Err(AdminError::TopicExists(String::from(topic)));
+        }
+        if !self.is_valid_name(topic) {
+            return Err(AdminError::InvalidTopicName(String::from(topic)));
+        }
+        let new_topic = self
+            .insert_topic(topic, config.as_ref())
+            .await
+            .map_err(AdminError::StorageError)?;
+        drop(creating);
+        Ok(self.describe(&new_topic).await)
+    }
+
+    /// Deletes topic `topic` with its log and disconnects clients connected to it
+    ///
+    /// # Errors
+    /// - [AdminError::UnknownTopic] if there is no such topic
+    /// - [AdminError::StorageError] if the topic log cannot be removed
+    pub async fn delete_topic(&self, topic: &str) -> Result<(), AdminError> {
+        // a topic with the same name is not created until the topic log is removed
+        let _creating = self.creating_topics.lock().await;
+        let removed = self.topics.lock().await.remove(topic);
+        let Some(removed) = removed else {
+            return Err(AdminError::UnknownTopic(String::from(topic)));
+        };
+        self.clients
+            .disconnect_topic(topic, DisconnectReason::TopicDeleted);
+        removed.delete().await.map_err(AdminError::StorageError)
+    }
+
+    /// All connected clients ordered by connection id
+    pub fn connections(&self) -> Vec<ConnectionDescription> {
+        self.clients.connections()
+    }
+
+    /// Disconnects the client with `connection_id` for the `reason`.
+    /// Returns `false` if there is no such client
+    pub fn disconnect_client(&self, connection_id: u64, reason: DisconnectReason) -> bool {
+        self.clients.disconnect(connection_id, reason)
+    }
+
+    /// Counters of connections of the `Server`
+    pub fn server_metrics(&self) -> &ServerMetrics {
+        &self.metrics
+    }
+
+    /// Metrics of topics and connections in the Prometheus text format
+    pub async fn metrics(&self) -> String {
+        let mut topics: Vec<Topic> = self.topics.lock().await.values().cloned().collect();
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        let roles: Vec<_> = self
+            .clients
+            .connections()
+            .into_iter()
+            .map(|connection| connection.role)
+            .collect();
+
+        let mut writer = MetricsWriter::new();
+        writer.topics(
+            &topics
+                .iter()
+                .map(|topic| (topic.name.clone(), topic.metrics.as_ref()))
+                .collect::<Vec<_>>(),
+        );
+        writer.clients(&roles);
+        writer.server(&self.metrics);
+        writer.finish()
+    }
+
+    /// Disconnects all clients, including clients connecting later, because the `Server` is shutting down
+    pub fn shutdown(&self) {
+        self.clients.close(DisconnectReason::Shutdown);
+    }
+
+    /// Loads the config with the config loader and applies it to the running `Server`.
+    /// Changes that cannot be applied are kept for the restart
+    ///
+    /// # Errors
+    /// - If there is no config loader, function will return [ErrorKind::Unsupported]
+    /// - If the config is not valid, function will return [ErrorKind::InvalidData]
+    /// - All other errors are inherited from the config loader, [Topic::open] and [Topic::set_config]
+    pub async fn reload(&self) -> io::Result<ConfigChanges> {
+        let changes = self.apply_config().await;
+        match &changes {
+            Ok(changes) => {
+                for change in &changes.applied {
+                    info!("Configuration reloaded: {}", change);
+                }
+                for change in &changes.restart_required {
+                    warn!("Configuration reloaded, restart required: {}", change);
+                }
+            }
+            Err(e) => error!("Failed to reload configuration: {}", e),
+        }
+        changes
     }
 
-    async fn get_sender(&self, topic: &str) -> Sender<Vec<u8>> {
-        self.get_topic(topic).await.sender.clone()
+    async fn apply_config(&self) -> io::Result<ConfigChanges> {
+        let Some(config_loader) = &self.config_loader else {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "the server has no configuration to reload",
+            ));
+        };
+        let mut settings = Settings::new(config_loader()?)?;
+        let config = &mut settings.config;
+        let current = self.settings.read().unwrap().config.clone();
+        let mut changes = ConfigChanges::default();
+
+        if config.listener != current.listener {
+            changes.restart_required.push(format!(
+                "listener {} -> {}",
+                current.listener, config.listener
+            ));
+            config.listener = current.listener.clone();
+        }
+        if config.unix_listener != current.unix_listener {
+            changes.restart_required.push(format!(
+                "unix_listener {:?} -> {:?}",
+                current.unix_listener, config.unix_listener
+            ));
+            config.unix_listener = current.unix_listener.clone();
+        }
+        if config.data_dir != current.data_dir {
+            changes.restart_required.push(format!(
+                "data_dir {:?} -> {:?}",
+                current.data_dir, config.data_dir
+            ));
+            config.data_dir = current.data_dir.clone();
+        }
+        if config.http_listener != current.http_listener {
+            changes.restart_required.push(format!(
+                "http_listener {:?} -> {:?}",
+                current.http_listener, config.http_listener
+            ));
+            config.http_listener = current.http_listener;
+        }
+        if config.tls != current.tls {
+            changes
+                .restart_required
+                .push(format!("tls {:?} -> {:?}", current.tls, config.tls));
+            config.tls = current.tls.clone();
+        }
+        if config.log_level != current.log_level {
+            log::set_max_level(config.log_level);
+            changes.applied.push(format!(
+                "log_level {} -> {}",
+                current.log_level, config.log_level
+            ));
+        }
+        if config.shutdown_timeout != current.shutdown_timeout {
+            changes.applied.push(format!(
+                "shutdown_timeout {:?} -> {:?}",
+                current.shutdown_timeout, config.shutdown_timeout
+            ));
+        }
+        if config.limits != current.limits {
+            changes.applied.push(format!(
+                "limits {:?} -> {:?}",
+                current.limits, config.limits
+            ));
+        }
+        if config.auth != current.auth {
+            changes
+                .applied
+                .push(format!("auth {:?} -> {:?}", current.auth, config.auth));
+        }
+        if config.acl != current.acl {
+            changes
+                .applied
+                .push(format!("acl {:?} -> {:?}", current.acl, config.acl));
+        }
+        if config.topics.default != current.topics.default {
+            changes.applied.push(format!(
+                "default topic config {:?} -> {:?}",
+                current.topics.default, config.topics.default
+            ));
+        }
+        if config.topics.topic_name_pattern != current.topics.topic_name_pattern {
+            changes.applied.push(format!(
+                "topic_name_pattern {:?} -> {:?}",
+                current.topics.topic_name_pattern, config.topics.topic_name_pattern
+            ));
+        }
+        if config.topics.auto_create != current.topics.auto_create {
+            changes.applied.push(format!(
+                "auto_create {} -> {}",
+                current.topics.auto_create, config.topics.auto_create
+            ));
+        }
+
+        let creating = self.creating_topics.lock().await;
+        for (topic_name, topic_config) in &config.topics.topics {
+            let existing = self.topics.lock().await.get(topic_name).cloned();
+            match existing {
+                Some(topic) => {
+                    let current_config = topic.config();
+                    if current_config == *topic_config {
+                        continue;
+                    }
+                    topic.set_config(topic_config.clone())?;
+                    changes.applied.push(format!(
+                        r#"config of topic "{}" {:?} -> {:?}"#,
+                        topic_name, current_config, topic_config
+                    ));
+                    if current_config.channel_capacity != topic_config.channel_capacity {
+                        changes.restart_required.push(format!(
+                            r#"channel_capacity of topic "{}" {} -> {}"#,
+                            topic_name,
+                            current_config.channel_capacity,
+                            topic_config.channel_capacity
+                        ));
+                    }
+                }
+                None => {
+                    self.insert_topic(topic_name, Some(topic_config)).await?;
+                    changes
+                        .applied
+                        .push(format!(r#"topic "{}" created"#, topic_name));
+                }
+            }
+        }
+        drop(creating);
+
+        *self.settings.write().unwrap() = settings;
+        Ok(changes)
     }
 
-    /// Creates [Client] according to the first message received from the connection
+    /// Creates [Client] according to the first message received from the connection.
+    /// `peer_principal` is the principal established by the connection, see [ClientStream::peer_principal](crate::server::stream::ClientStream::peer_principal)
     ///
     /// # Errors
     /// - [ClientConnectionError::UnexpectedMessage] if connection message is not in correct format
+    ///   or is bigger than [Limits::max_frame_size]
     /// - [ClientConnectionError::Aborted] if connection is aborted
-    /// - [ClientConnectionError::ReaderError] if some other [TcpStream::read] error occurs
-    pub async fn new_client(
+    /// - [ClientConnectionError::TimedOut] if connection message is not received within [CONNECTION_MESSAGE_TIMEOUT]
+    /// - [ClientConnectionError::ReaderError] if some other error of reading the stream occurs
+    /// - [ClientConnectionError::AuthenticationFailed] if credentials are missing or not accepted
+    /// - [ClientConnectionError::AccessDenied] if the client is not allowed to connect to a topic or as an admin
+    /// - [ClientConnectionError::UnknownTopic] if there is no such topic and topics cannot be created by clients
+    /// - [ClientConnectionError::InvalidTopicName] if a new topic name does not match the topic name pattern
+    /// - [ClientConnectionError::DuplicateName] if another connected client already has the name
+    /// - [ClientConnectionError::StorageError] if the topic log cannot be opened
+    pub async fn new_client<S: AsyncRead + Unpin>(
         &self,
-        socket_addr: SocketAddr,
-        buf_reader: &mut BufReader<&mut TcpStream>,
+        connection_id: u64,
+        socket_addr: ClientAddr,
+        peer_principal: Option<String>,
+        buf_reader: &mut BufReader<S>,
     ) -> Result<Client, ClientConnectionError> {
         let mut buffer: Vec<u8> = vec![];
+        // a line longer than the frame size limit is not read to the end
+        let max_line = self.limits().max_frame_size as u64 + 1;
 
-        match buf_reader.read_until(DELIMITER, &mut buffer).await {
+        let read = timeout(
+            CONNECTION_MESSAGE_TIMEOUT,
+            AsyncReadExt::take(&mut *buf_reader, max_line).read_until(DELIMITER, &mut buffer),
+        )
+        .await
+        .map_err(|_| ClientConnectionError::TimedOut)?;
+        match read {
             Ok(0) => Err(ClientConnectionError::Aborted),
             Ok(sz) => {
                 if buffer[sz - 1] != DELIMITER {
-                    return Err(ClientConnectionError::UnexpectedMessage(buffer));
+                    return Err(ClientConnectionError::UnexpectedMessage(
+                        match sz as u64 == max_line {
+                            true => format!("bigger than {} bytes", max_line - 1),
+                            false => "not ended with a line break".to_string(),
+                        },
+                    ));
                 }
                 buffer.pop();
                 let message = String::from_utf8_lossy(&buffer);
-                match serde_json::from_str::<ConnectionMessage>(&message) {
-                    Ok(connection_message) => match connection_message.client_type {
-                        ClientType::Subscriber => Ok(Client::Subscriber(Subscriber::new(
-                            socket_addr,
-                            connection_message.topic.clone(),
-                            self.get_receiver(&connection_message.topic).await,
-                        ))),
-                        ClientType::Publisher => Ok(Client::Publisher(Publisher::new(
-                            socket_addr,
-                            connection_message.topic.clone(),
-                            self.get_sender(&connection_message.topic).await,
-                        ))),
-                    },
-                    Err(_) => Err(ClientConnectionError::UnexpectedMessage(buffer.to_vec())),
-                }
+                // the message itself is not kept, as it can contain credentials
+                let connection_message = serde_json::from_str::<ConnectionMessage>(&message)
+                    .map_err(|e| ClientConnectionError::UnexpectedMessage(e.to_string()))?;
+                Self::validate(&connection_message)
+                    .map_err(ClientConnectionError::UnexpectedMessage)?;
+                let principal = self
+                    .authenticate(connection_message.auth.clone(), peer_principal)
+                    .await?;
+                self.build_client(connection_id, socket_addr, principal, connection_message)
+                    .await
             }
             Err(e) if e.kind() == ErrorKind::ConnectionAborted => {
                 Err(ClientConnectionError::Aborted)
@@ -88,4 +582,389 @@ impl ServerBroker {
             Err(_) => Err(ClientConnectionError::ReaderError),
         }
     }
+
+    /// Returns the principal of the client with `credentials`.
+    /// Clients without credentials get `peer_principal` established by their connection.
+    /// Otherwise, if clients are not authenticated or anonymous clients are allowed, clients without credentials get `None`
+    ///
+    /// # Errors
+    /// [ClientConnectionError::AuthenticationFailed] if credentials are missing or not accepted
+    async fn authenticate(
+        &self,
+        credentials: Option<Credentials>,
+        peer_principal: Option<String>,
+    ) -> Result<Option<String>, ClientConnectionError> {
+        if credentials.is_none() && peer_principal.is_some() {
+            return Ok(peer_principal);
+        }
+        let (authenticators, allow_anonymous) = {
+            let settings = self.settings.read().unwrap();
+            let authenticators: Vec<Arc<dyn Authenticator>> = self
+                .authenticators
+                .iter()
+                .chain(settings.authenticators.iter())
+                .cloned()
+                .collect();
+            (authenticators, settings.config.auth.allow_anonymous)
+        };
+        if authenticators.is_empty() {
+            return Ok(None); // clients are not authenticated
+        }
+        let Some(credentials) = credentials else {
+            return match allow_anonymous {
+                true => Ok(None),
+                false => Err(ClientConnectionError::AuthenticationFailed(
+                    "client without credentials".to_string(),
+                )),
+            };
+        };
+
+        let identity = credentials.identity();
+        // password hashes are slow to verify by design, so the async runtime is not blocked
+        let principal = spawn_blocking(move || {
+            authenticators
+                .iter()
+                .find_map(|authenticator| authenticator.authenticate(&credentials))
+        })
+        .await
+        .ok()
+        .flatten();
+        match principal {
+            Some(principal) => Ok(Some(principal)),
+            None => Err(ClientConnectionError::AuthenticationFailed(identity)),
+        }
+    }
+
+    /// Checks that the client with `principal` may connect as `client_type` to `topic_names`.
+    /// Topics matching topic filters are checked when they are subscribed.
+    /// A client with its own `topic_config` must be allowed to manage the server, as it configures new topics
+    ///
+    /// # Errors
+    /// [ClientConnectionError::AccessDenied] with the first denied action
+    fn authorize(
+        &self,
+        principal: Option<&str>,
+        client_type: ClientType,
+        topic_names: &[String],
+        topic_config: bool,
+    ) -> Result<(), ClientConnectionError> {
+        let settings = self.settings.read().unwrap();
+        let acl = &settings.config.acl;
+        let denied = match client_type {
+            _ if topic_config && !acl.is_allowed(principal, Permission::Admin, None) => {
+                Some("configure topics".to_string())
+            }
+            ClientType::Admin => (!acl.is_allowed(principal, Permission::Admin, None))
+                .then(|| "manage the server".to_string()),
+            ClientType::Publisher | ClientType::Subscriber => {
+                let permission = match client_type {
+                    ClientType::Publisher => Permission::Publish,
+                    _ => Permission::Subscribe,
+                };
+                topic_names
+                    .iter()
+                    .find(|topic| !acl.is_allowed(principal, permission, Some(topic)))
+                    .map(|topic| format!(r#"{} to topic "{}""#, permission, topic))
+            }
+        };
+        match denied {
+            Some(action) => Err(ClientConnectionError::AccessDenied(denied_action(
+                principal, &action,
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the client can be built from `connection_message`:
+    /// there is at least one topic (admins are not connected to topics), topic filters are valid and used only by subscribers,
+    /// a publisher of raw messages has one topic, as it cannot choose the topic of a message, and the topic config can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    fn validate(connection_message: &ConnectionMessage) -> Result<(), String> {
+        let topic_names = connection_message.topic_names();
+        if connection_message.client_type != ClientType::Admin && topic_names.is_empty() {
+            return Err("no topic given".to_string());
+        }
+        if let Some(name) = topic_names.iter().find(|name| {
+            TopicFilter::is_filter(name)
+                && (connection_message.client_type != ClientType::Subscriber
+                    || TopicFilter::parse(name).is_none())
+        }) {
+            return Err(format!(r#"topic filter "{}" is not allowed"#, name));
+        }
+        if connection_message.client_type == ClientType::Publisher
+            && connection_message.format == MessageFormat::Raw
+            && topic_names.len() > 1
+        {
+            return Err("publisher of raw messages can have only one topic".to_string());
+        }
+        match &connection_message.config {
+            Some(config) => config.validate().map_err(|e| format!("config: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    async fn build_client(
+        &self,
+        connection_id: u64,
+        socket_addr: ClientAddr,
+        principal: Option<String>,
+        connection_message: ConnectionMessage,
+    ) -> Result<Client, ClientConnectionError> {
+        let max_frame_size = self.limits().max_frame_size;
+        let (filters, topic_names): (Vec<String>, Vec<String>) =
+            match connection_message.client_type {
+                ClientType::Admin => (vec![], vec![]), // admins are not connected to topics
+                _ => connection_message
+                    .topic_names()
+                    .into_iter()
+                    .partition(|name| TopicFilter::is_filter(name)),
+            };
+        self.authorize(
+            principal.as_deref(),
+            connection_message.client_type,
+            &topic_names,
+            connection_message.config.is_some(),
+        )?;
+        // all checks are done before topics are created, so that a rejected client leaves no new topics
+        for topic_name in &topic_names {
+            if !self.topics.lock().await.contains_key(topic_name) {
+                self.check_new_topic(topic_name)?;
+            }
+        }
+        let _name = self.clients.reserve_name(connection_message.name.clone())?;
+        let mut topics = vec![];
+        for topic_name in topic_names {
+            topics.push(
+                self.get_topic(&topic_name, connection_message.config.as_ref())
+                    .await?,
+            );
+        }
+
+        match connection_message.client_type {
+            ClientType::Subscriber => {
+                let watch = match filters.is_empty() {
+                    true => None,
+                    false => {
+                        let filters = filters
+                            .iter()
+                            .filter_map(|filter| TopicFilter::parse(filter))
+                            .collect();
+                        // access to topics matching the filters is checked by the current ACL when they are matched
+                        let settings = self.settings.clone();
+                        let subscriber = principal.clone();
+                        let mut watch = TopicWatch::new(
+                            filters,
+                            self.topics.clone(),
+                            self.topics_changed.subscribe(),
+                            move |topic| {
+                                settings.read().unwrap().config.acl.is_allowed(
+                                    subscriber.as_deref(),
+                                    Permission::Subscribe,
+                                    Some(topic),
+                                )
+                            },
+                        );
+                        let subscribed: Vec<String> =
+                            topics.iter().map(|topic| topic.name.clone()).collect();
+                        topics.extend(watch.matching(&subscribed).await);
+                        Some(watch)
+                    }
+                };
+
+                let registration = self.register(
+                    connection_id,
+                    socket_addr,
+                    principal,
+                    &connection_message,
+                    &topics,
+                )?;
+                let mut subscriptions = vec![];
+                for topic in topics {
+                    subscriptions.push(
+                        Subscription::open(
+                            topic,
+                            connection_message.start,
+                            connection_message.group.as_deref(),
+                        )
+                        .await
+                        .map_err(ClientConnectionError::StorageError)?,
+                    );
+                }
+                Ok(Client::Subscriber(Subscriber::new(
+                    registration,
+                    subscriptions,
+                    watch,
+                    connection_message.framing,
+                    connection_message.format,
+                    max_frame_size,
+                )))
+            }
+            ClientType::Publisher => Ok(Client::Publisher(Publisher::new(
+                self.register(
+                    connection_id,
+                    socket_addr,
+                    principal,
+                    &connection_message,
+                    &topics,
+                )?,
+                topics,
+                connection_message.framing,
+                connection_message.format,
+                connection_message.ack,
+                max_frame_size,
+            ))),
+            ClientType::Admin => Ok(Client::Admin(Admin::new(
+                self.register(
+                    connection_id,
+                    socket_addr,
+                    principal,
+                    &connection_message,
+                    &topics,
+                )?,
+                connection_message.framing,
+                max_frame_size,
+            ))),
+        }
+    }
+
+    fn register(
+        &self,
+        connection_id: u64,
+        socket_addr: ClientAddr,
+        principal: Option<String>,
+        connection_message: &ConnectionMessage,
+        topics: &[Topic],
+    ) -> Result<Registration, ClientConnectionError> {
+        self.clients.register(
+            connection_id,
+            ClientIdentity {
+                name: connection_message.name.clone(),
+                principal,
+            },
+            socket_addr,
+            connection_message.client_type,
+            topics.iter().map(|topic| topic.name.clone()).collect(),
+            connection_message.presence,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+    /// Sends `message` as the start of a new connection and creates the client.
+    /// Returns the result together with the client end of the stream, which must be kept open
+    async fn new_client(
+        server_broker: &ServerBroker,
+        message: &[u8],
+    ) -> (Result<Client, ClientConnectionError>, DuplexStream) {
+        let (mut client_end, server_end) = duplex(1024);
+        let mut server_end = BufReader::new(server_end);
+        client_end.write_all(message).await.unwrap();
+        let client = server_broker
+            .new_client(
+                server_broker.next_connection_id(),
+                ClientAddr::Tcp("127.0.0.1:5000".parse().unwrap()),
+                None,
+                &mut server_end,
+            )
+            .await;
+        (client, client_end)
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_message() {
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let (client, _client_end) = new_client(
+            &server_broker,
+            b"{\"method\": \"publish\", \"topic\": \"events/#\", \"auth\": {\"username\": \"alice\", \"password\": \"secret\"}}\n",
+        )
+        .await;
+        // the reason is logged, so it does not contain credentials
+        assert!(matches!(
+            client.err(),
+            Some(ClientConnectionError::UnexpectedMessage(reason))
+                if reason == r#"topic filter "events/#" is not allowed"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_client_creates_no_topics() {
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let (client, _client_end) = new_client(
+            &server_broker,
+            b"{\"method\": \"publish\", \"topic\": \"events\", \"name\": \"sensor\"}\n",
+        )
+        .await;
+        assert!(client.is_ok());
+
+        let (client, _) = new_client(
+            &server_broker,
+            b"{\"method\": \"publish\", \"topic\": \"other_events\", \"name\": \"sensor\"}\n",
+        )
+        .await;
+        assert!(matches!(
+            client.err(),
+            Some(ClientConnectionError::DuplicateName(_))
+        ));
+        let (client, _) = new_client(
+            &server_broker,
+            b"{\"method\": \"subscribe\", \"topics\": [\"other_events\", \"x\"]}\n",
+        )
+        .await;
+        assert!(matches!(
+            client.err(),
+            Some(ClientConnectionError::InvalidTopicName(_))
+        ));
+
+        let topics: Vec<String> = server_broker
+            .describe_topics()
+            .await
+            .into_iter()
+            .map(|topic| topic.name)
+            .collect();
+        assert_eq!(topics, vec!["events".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_raw_publisher_topics() {
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let (client, _client_end) = new_client(
+            &server_broker,
+            b"{\"method\": \"publish\", \"topic\": \"events\", \"topics\": [\"other_events\"]}\n",
+        )
+        .await;
+        assert!(matches!(
+            client.err(),
+            Some(ClientConnectionError::UnexpectedMessage(reason))
+                if reason == "publisher of raw messages can have only one topic"
+        ));
+
+        let (client, _client_end) = new_client(
+            &server_broker,
+            b"{\"method\": \"publish\", \"topic\": \"events\", \"topics\": [\"other_events\"], \"format\": \"json\"}\n",
+        )
+        .await;
+        assert!(matches!(client, Ok(Client::Publisher(_))));
+        let (client, _client_end) = new_client(
+            &server_broker,
+            b"{\"method\": \"subscribe\", \"topic\": \"events\", \"topics\": [\"other_events\"]}\n",
+        )
+        .await;
+        assert!(matches!(client, Ok(Client::Subscriber(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_connection_message_timeout() {
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let (client, _client_end) = new_client(&server_broker, b"{\"method\": ").await;
+        assert!(matches!(
+            client.err(),
+            Some(ClientConnectionError::TimedOut)
+        ));
+    }
 }
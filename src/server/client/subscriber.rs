@@ -1,79 +1,849 @@
-use log::debug;
+use crate::server::acl::{denied_action, Permission};
+use crate::server::client::registry::{DisconnectSignal, Registration};
+use crate::server::connection_message::{MessageFormat, StartPosition};
+use crate::server::framing::Framing;
+use crate::server::service_message::{DisconnectReason, ErrorCode, ServiceMessage};
+use crate::server::stream::{write_frame, ClientAddr};
+use crate::server::subscriber_message::SubscriberMessage;
+use crate::server::topic::config::SlowConsumerPolicy;
+use crate::server::topic::filter::TopicFilter;
+use crate::server::topic::group::ConsumerGroup;
+use crate::server::topic::{Cursor, Fetch, Record, Topic};
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::future::{poll_fn, Future};
 use std::io;
 use std::io::{Error, ErrorKind};
-use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::Poll;
 use std::time::Duration;
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast::Receiver;
+use tokio::sync::{watch, Mutex};
 use tokio::time::timeout;
 
+/// When the `Server` is shutting down, messages are sent to the `Subscriber` until none arrives within this time
+const DRAIN_IDLE_TIME: Duration = Duration::from_millis(100);
+
 /// A `Subscriber` is one of the `Client` types being served
 ///
-/// `Subscriber` keeps it in itself `socket_addr` (ip/port of connected `Client`) and `topic` (connected `Topic` name)
+/// `Subscriber` keeps it in itself `connection_id` (server-unique id of the connection), `name` (unique name, if any),
+/// `socket_addr` (ip/port or process credentials of connected `Client`, see [ClientAddr]), `topics` (connected `Topic` names),
+/// `filters` (connected topic filters), `group` (consumer group name, if any),
+/// `framing` (how messages are separated in the connection) and `format` (format of delivered messages)
 ///
 /// The `Subscriber` client listens to messages sent by each [Publisher](crate::server::client::publisher::Publisher)
-/// that are connected to the same topic as the `Subscriber`
+/// that are connected to the topics of the `Subscriber`. Subscribers of the same group share the messages
+///
+/// Topics matching a [TopicFilter] are added to `topics` when they are created.
+/// Matching topics the `Subscriber` is not allowed to subscribe to are reported to the client
+///
+/// If the `Subscriber` falls behind the messages published in real time,
+/// the [SlowConsumerPolicy] of the topic is applied
 pub struct Subscriber {
-    pub socket_addr: SocketAddr,
-    pub topic: String,
-    receiver: Receiver<Vec<u8>>,
+    pub connection_id: u64,
+    pub name: Option<String>,
+    pub socket_addr: ClientAddr,
+    pub topics: Vec<String>,
+    pub filters: Vec<String>,
+    pub group: Option<String>,
+    pub framing: Framing,
+    pub format: MessageFormat,
+    subscriptions: Vec<Subscription>,
+    watch: Option<TopicWatch>,
+    next_subscription: usize, // polled first, so that no topic is starved
+    max_frame_size: usize,
+    registration: Registration,
+    disconnect: DisconnectSignal,
+}
+
+/// Delivery of messages of one topic to the `Subscriber` client
+///
+/// `position` is the offset of the message following the last one sent to the client
+pub(crate) struct Subscription {
+    position: u64,
+    source: Topic,
+    receiver: Option<Receiver<Record>>,
+    cursor: Option<Cursor>,
+    member: Option<GroupMember>,
+}
+
+/// Topics matching the topic filters of a `Subscriber`
+pub(crate) struct TopicWatch {
+    filters: Vec<TopicFilter>,
+    topics: Arc<Mutex<HashMap<String, Topic>>>,
+    changed: watch::Receiver<u64>, // notified when topics are created
+    pending: VecDeque<WatchEvent>, // matching topics not subscribed or reported yet
+    denied: HashSet<String>,       // matching topics the subscriber is not allowed to
+    allowed: Box<dyn Fn(&str) -> bool + Send + Sync>, // whether the subscriber may subscribe to the topic
+}
+
+/// A new topic matching the filters of a [TopicWatch]
+enum WatchEvent {
+    Allowed(Topic),
+    Denied(String),
+}
+
+/// What is sent to the `Subscriber` client next
+enum Delivery {
+    Record(Record),
+    Service(ServiceMessage),
+    Disconnect(ServiceMessage),
 }
 
 impl Subscriber {
-    pub fn new(socket_addr: SocketAddr, topic: String, receiver: Receiver<Vec<u8>>) -> Self {
+    pub(crate) fn new(
+        registration: Registration,
+        subscriptions: Vec<Subscription>,
+        watch: Option<TopicWatch>,
+        framing: Framing,
+        format: MessageFormat,
+        max_frame_size: usize,
+    ) -> Self {
         Self {
-            socket_addr,
-            topic,
-            receiver,
+            connection_id: registration.connection_id,
+            name: registration.name.clone(),
+            socket_addr: registration.socket_addr,
+            topics: subscriptions
+                .iter()
+                .map(|subscription| subscription.source.name.clone())
+                .collect(),
+            filters: watch.as_ref().map_or(vec![], |watch| {
+                watch
+                    .filters
+                    .iter()
+                    .map(|filter| filter.pattern.clone())
+                    .collect()
+            }),
+            group: subscriptions
+                .iter()
+                .find_map(|subscription| subscription.member.as_ref())
+                .map(|member| member.group.name.clone()),
+            framing,
+            format,
+            subscriptions,
+            watch,
+            next_subscription: 0,
+            max_frame_size,
+            disconnect: registration.disconnect_signal(),
+            registration,
         }
     }
 
+    /// Offsets of the messages following the last ones sent to the client, by topic
+    pub fn positions(&self) -> Vec<(String, u64)> {
+        self.subscriptions
+            .iter()
+            .map(|subscription| (subscription.source.name.clone(), subscription.position))
+            .collect()
+    }
+
     /// Start listening messages from [Publishers](crate::server::client::publisher::Publisher)
-    pub async fn listen(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+    ///
+    /// Without a group, first the messages already stored in the topic log are sent starting from `position`,
+    /// then the `Subscriber` switches to messages published in real time.
+    /// In a group, the `Subscriber` gets messages handed out to it by the consumer group.
+    /// Messages of all topics are merged into one stream.
+    /// Topics matching the topic filters are subscribed from the earliest message when they are created
+    ///
+    /// Messages are sent and [SubscriberMessages](SubscriberMessage) sent by the client are read in the negotiated [Framing].
+    /// Clients with presence events are notified about other clients of their topics.
+    /// When the `Server` is shutting down, messages already published are sent before disconnecting
+    ///
+    /// The client connection read through `buf_reader` can be any stream: TCP, TLS or an in-memory [duplex](tokio::io::duplex)
+    ///
+    /// # Errors
+    /// - If the topic log cannot be read, function will return error from [Topic::fetch]
+    /// - If publisher-subscriber channel will aborted or the `Subscriber` is disconnected
+    ///   by [SlowConsumerPolicy::Disconnect], function will return [ErrorKind::BrokenPipe]
+    /// - If committed offset cannot be stored, function will return error from [ConsumerGroup::commit]
+    /// - All other errors are inherited from reading and writing the stream
+    pub async fn listen<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        buf_reader: &mut BufReader<S>,
+    ) -> io::Result<()> {
+        let mut incoming: Vec<u8> = vec![];
+        let framing = self.framing;
         loop {
-            let data = timeout(Duration::from_secs_f64(1.0), self.receiver.recv()).await;
-            let data = match data {
-                Ok(data) => data,
-                Err(_) => {
-                    let result = timeout(Duration::from_secs_f64(0.2), async {
-                        let mut buf = [0u8; 1];
-                        let peak_result = stream.peek(&mut buf).await;
-                        peak_result.is_ok() && peak_result.unwrap() > 0usize
-                    })
-                    .await;
-                    match result {
-                        Ok(result) => {
-                            if result {
-                                continue;
-                            }
-                            debug!("Disconnect {}", &self.socket_addr);
-                            break Ok(()); // disconnected
+            tokio::select! {
+                read = framing.read_frame(buf_reader, &mut incoming, self.max_frame_size) => match read {
+                    Ok(None) => {
+                        debug!("Disconnect {}", &self.registration);
+                        break Ok(());
+                    }
+                    Ok(Some(message)) => self.handle_message(&message).await?,
+                    Err(e) if e.kind() == ErrorKind::ConnectionAborted => {
+                        debug!("Aborted disconnect {}", &self.registration);
+                        break Ok(());
+                    }
+                    Err(e) => break Err(e),
+                },
+                event = TopicWatch::next_topic(&mut self.watch, &self.topics) => match event {
+                    WatchEvent::Allowed(topic) => {
+                        let topic_name = topic.name.clone();
+                        let subscription =
+                            Subscription::open(topic, StartPosition::Earliest, self.group.as_deref())
+                                .await?;
+                        info!(
+                            r#"Subscriber {} subscribed to new topic "{}" matching {:?}"#,
+                            self.registration, topic_name, self.filters
+                        );
+                        self.registration.add_topic(topic_name.clone());
+                        self.topics.push(topic_name);
+                        self.subscriptions.push(subscription);
+                    }
+                    WatchEvent::Denied(topic_name) => {
+                        let message = denied_action(
+                            self.registration.principal.as_deref(),
+                            &format!(r#"{} to topic "{}""#, Permission::Subscribe, topic_name),
+                        );
+                        warn!(
+                            "Subscriber {} not subscribed to topic matching {:?}: {}",
+                            self.registration, self.filters, message
+                        );
+                        let message = ServiceMessage::Error {
+                            code: ErrorCode::AccessDenied,
+                            message,
+                        };
+                        if let Err(e) = write_frame(buf_reader.get_mut(), &message.to_frame(framing)).await {
+                            break Err(e);
+                        }
+                    }
+                },
+                event = self.registration.next_event() => {
+                    let stream = buf_reader.get_mut();
+                    if let Err(e) = write_frame(stream, &event.to_frame(framing)).await {
+                        break Err(e);
+                    }
+                },
+                reason = self.disconnect.wait() => {
+                    let stream = buf_reader.get_mut();
+                    if reason == DisconnectReason::Shutdown {
+                        if let Err(e) = self.drain(stream).await {
+                            break Err(e);
+                        }
+                    }
+                    let message = ServiceMessage::Disconnected { reason };
+                    let _ = write_frame(stream, &message.to_frame(framing)).await;
+                    break Ok(());
+                },
+                (index, delivery) = Self::next_delivery(&mut self.subscriptions, self.next_subscription) => {
+                    self.next_subscription = (index + 1) % self.subscriptions.len();
+                    let stream = buf_reader.get_mut();
+                    let result = match delivery? {
+                        Delivery::Record(record) => self.send(stream, index, record).await,
+                        Delivery::Service(message) => write_frame(stream, &message.to_frame(self.framing)).await,
+                        Delivery::Disconnect(message) => {
+                            let _ = write_frame(stream, &message.to_frame(self.framing)).await;
+                            break Err(Error::new(
+                                ErrorKind::BrokenPipe,
+                                "subscriber fell too far behind",
+                            ));
                         }
-                        Err(_) => continue,
                     };
+                    match result {
+                        Ok(_) => {}
+                        Err(e) if e.kind() == ErrorKind::ConnectionAborted => {
+                            debug!("Aborted disconnect {}", &self.registration);
+                            break Ok(());
+                        }
+                        Err(e) => break Err(e),
+                    }
+                },
+            }
+        }
+    }
+
+    /// Waits for the next message of any subscription to be sent to the client.
+    /// Subscriptions are polled starting from `first`. Returns the index of the subscription
+    async fn next_delivery(
+        subscriptions: &mut [Subscription],
+        first: usize,
+    ) -> (usize, io::Result<Delivery>) {
+        let count = subscriptions.len();
+        let mut deliveries: Vec<_> = subscriptions
+            .iter_mut()
+            .map(|subscription| Box::pin(subscription.next_delivery()))
+            .collect();
+        poll_fn(|cx| {
+            for i in 0..count {
+                let index = (first + i) % count;
+                if let Poll::Ready(delivery) = deliveries[index].as_mut().poll(cx) {
+                    return Poll::Ready((index, delivery));
                 }
-            };
+            }
+            Poll::Pending
+        })
+        .await
+    }
 
-            match data {
-                Err(_) => break Err(Error::from(ErrorKind::BrokenPipe)),
-                Ok(message) => match stream.write(message.as_slice()).await {
-                    Ok(_) => {
-                        debug!(
-                            "Send message to subscriber {}: \"{}\"",
-                            self.socket_addr,
-                            String::from_utf8_lossy(message.as_slice())
-                                .strip_suffix("\n")
-                                .unwrap()
-                        );
+    /// Sends messages already published to the topics of the `Subscriber`,
+    /// until no message arrives within [DRAIN_IDLE_TIME]
+    async fn drain<W: AsyncWrite + Unpin>(&mut self, stream: &mut W) -> io::Result<()> {
+        while let Ok((index, delivery)) = timeout(
+            DRAIN_IDLE_TIME,
+            Self::next_delivery(&mut self.subscriptions, self.next_subscription),
+        )
+        .await
+        {
+            self.next_subscription = (index + 1) % self.subscriptions.len();
+            match delivery? {
+                Delivery::Record(record) => self.send(stream, index, record).await?,
+                Delivery::Service(message) => {
+                    write_frame(stream, &message.to_frame(self.framing)).await?
+                }
+                Delivery::Disconnect(_) => break,
+            }
+        }
+        Ok(())
+    }
+
+    async fn send<W: AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut W,
+        index: usize,
+        record: Record,
+    ) -> io::Result<()> {
+        let subscription = &mut self.subscriptions[index];
+        let topic = subscription.source.name.clone();
+        let message = match self.format {
+            MessageFormat::Raw => self.framing.encode(record.payload.as_slice()),
+            MessageFormat::Json => ServiceMessage::Message {
+                publisher_name: record.metadata.publisher_name.clone(),
+                publisher_id: record.metadata.connection_id,
+                from_topic: topic.clone(),
+                message_text: String::from_utf8_lossy(record.payload.as_slice()).into_owned(),
+                time: record.metadata.time,
+                key: record.metadata.key.clone(),
+                headers: record.metadata.headers.clone(),
+            }
+            .to_frame(self.framing),
+        };
+        write_frame(stream, &message).await?;
+        subscription.delivered(record.offset);
+        subscription.source.metrics.delivered(record.payload.len());
+        debug!(
+            r#"Send message {} of topic "{}" to subscriber {}: "{}""#,
+            record.offset,
+            topic,
+            self.registration,
+            String::from_utf8_lossy(record.payload.as_slice())
+        );
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, message: &[u8]) -> io::Result<()> {
+        let message = String::from_utf8_lossy(message);
+        match serde_json::from_str::<SubscriberMessage>(&message) {
+            Ok(SubscriberMessage::Commit { topic, offset }) => {
+                for subscription in &self.subscriptions {
+                    if topic
+                        .as_ref()
+                        .is_some_and(|topic| *topic != subscription.source.name)
+                    {
+                        continue;
                     }
-                    Err(e) if e.kind() == ErrorKind::ConnectionAborted => {
-                        debug!("Aborted disconnect {}", &self.socket_addr);
-                        break Ok(());
+                    match &subscription.member {
+                        Some(member) => {
+                            let committed = member.group.commit(member.id, offset).await?;
+                            debug!(
+                                r#"Subscriber {} committed group "{}" of topic "{}" up to offset {}"#,
+                                self.registration, member.group.name, member.topic, committed
+                            );
+                        }
+                        None => debug!(
+                            r#"Subscriber {} without group tried to commit topic "{}", ignoring"#,
+                            self.registration, subscription.source.name
+                        ),
+                    }
+                }
+            }
+            Err(_) => debug!(
+                r#"Unexpected message from subscriber {}: "{}", ignoring"#,
+                self.registration,
+                message.trim_end()
+            ),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Subscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.registration.fmt(f)
+    }
+}
+
+impl Subscription {
+    /// Subscribes to `source` starting from `start` or, in consumer group `group`,
+    /// from the committed offset of the group (`start` is used only when the group is created)
+    ///
+    /// # Errors
+    /// All errors are inherited from [Topic::group]
+    pub async fn open(
+        source: Topic,
+        start: StartPosition,
+        group: Option<&str>,
+    ) -> io::Result<Self> {
+        let (start_offset, next_offset) = source.offsets().await;
+        let mut position = match start {
+            StartPosition::Earliest => start_offset,
+            StartPosition::Latest => next_offset,
+            StartPosition::Offset(offset) => offset.clamp(start_offset, next_offset),
+        };
+        let member = match group {
+            Some(group_name) => {
+                let group = source.group(group_name, position).await?;
+                position = group.committed();
+                Some(GroupMember::join(group, &source))
+            }
+            None => None,
+        };
+        Ok(Self {
+            position,
+            source,
+            receiver: None,
+            cursor: None,
+            member,
+        })
+    }
+
+    /// Waits for the next message of the topic to be sent to the client
+    async fn next_delivery(&mut self) -> io::Result<Delivery> {
+        if let Some(member) = &mut self.member {
+            return member.next_record(&self.source).await.map(Delivery::Record);
+        }
+
+        let policy = self.source.config().slow_consumer_policy;
+        loop {
+            match &mut self.receiver {
+                None => match self.source.fetch(self.position).await? {
+                    Fetch::Record(record) => return Ok(Delivery::Record(record)),
+                    Fetch::UpToDate(receiver) => {
+                        if policy == SlowConsumerPolicy::Block && self.cursor.is_none() {
+                            self.cursor = Some(self.source.cursor(self.position));
+                        }
+                        self.receiver = Some(receiver);
+                    }
+                },
+                Some(receiver) => match receiver.recv().await {
+                    Ok(record) if record.offset < self.position => continue, // already sent from the log
+                    Ok(record) => return Ok(Delivery::Record(record)),
+                    Err(RecvError::Lagged(count)) => {
+                        self.source.metrics.lagged();
+                        match policy {
+                            SlowConsumerPolicy::DropOldest => {
+                                return Ok(Delivery::Service(ServiceMessage::MessagesSkipped {
+                                    topic: self.source.name.clone(),
+                                    count,
+                                }))
+                            }
+                            SlowConsumerPolicy::Block => self.receiver = None, // catch up from the log
+                            SlowConsumerPolicy::Disconnect => {
+                                return Ok(Delivery::Disconnect(ServiceMessage::Disconnected {
+                                    reason: DisconnectReason::SlowConsumer,
+                                }))
+                            }
+                        }
                     }
-                    Err(e) => break Err(Error::from(e)),
+                    Err(RecvError::Closed) => return Err(Error::from(ErrorKind::BrokenPipe)),
                 },
             }
         }
     }
+
+    /// Moves the subscription past the message sent to the client
+    fn delivered(&mut self, offset: u64) {
+        self.position = offset + 1;
+        if let Some(cursor) = &self.cursor {
+            cursor.update(self.position);
+        }
+        if let Some(member) = &mut self.member {
+            member.delivered(offset);
+        }
+    }
+}
+
+impl TopicWatch {
+    /// Watches `topics` matching `filters`. Topics for which `allowed` returns `false` are reported as denied once
+    pub fn new(
+        filters: Vec<TopicFilter>,
+        topics: Arc<Mutex<HashMap<String, Topic>>>,
+        changed: watch::Receiver<u64>,
+        allowed: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            filters,
+            topics,
+            changed,
+            pending: VecDeque::default(),
+            denied: HashSet::default(),
+            allowed: Box::new(allowed),
+        }
+    }
+
+    /// Returns existing allowed topics matching the filters, except `subscribed` ones, ordered by name.
+    /// Matching topics denied for the first time are reported by [TopicWatch::next_topic]
+    pub async fn matching(&mut self, subscribed: &[String]) -> Vec<Topic> {
+        self.changed.borrow_and_update();
+        let topics = self.topics.lock().await;
+        let mut matching = vec![];
+        let mut denied = vec![];
+        for topic in topics.values().filter(|topic| {
+            !subscribed.contains(&topic.name)
+                && self
+                    .filters
+                    .iter()
+                    .any(|filter| filter.matches(&topic.name))
+        }) {
+            if (self.allowed)(&topic.name) {
+                matching.push(topic.clone());
+            } else if self.denied.insert(topic.name.clone()) {
+                denied.push(topic.name.clone());
+            }
+        }
+        matching.sort_by(|a, b| a.name.cmp(&b.name));
+        denied.sort();
+        self.pending
+            .extend(denied.into_iter().map(WatchEvent::Denied));
+        matching
+    }
+
+    /// Waits for a new topic matching the filters of `watch`. Without `watch` waits forever
+    async fn next_topic(watch: &mut Option<TopicWatch>, subscribed: &[String]) -> WatchEvent {
+        let Some(watch) = watch else {
+            return std::future::pending().await;
+        };
+        loop {
+            if let Some(event) = watch.pending.pop_front() {
+                return event;
+            }
+            if watch.changed.changed().await.is_err() {
+                return std::future::pending().await;
+            }
+            let matching = watch.matching(subscribed).await;
+            watch
+                .pending
+                .extend(matching.into_iter().map(WatchEvent::Allowed));
+        }
+    }
+}
+
+/// Membership of a [Subscriber] in a [ConsumerGroup]. The member leaves the group when dropped
+pub(crate) struct GroupMember {
+    group: Arc<ConsumerGroup>,
+    topic: String,
+    id: u64,
+    receiver: Receiver<Record>, // notifies about new messages in the topic
+    rebalances: watch::Receiver<u64>,
+    claimed: Option<u64>, // handed out to the member but not sent yet
+}
+
+impl GroupMember {
+    pub fn join(group: Arc<ConsumerGroup>, topic: &Topic) -> Self {
+        let receiver = topic.sender.subscribe();
+        let (id, rebalances) = group.join(&topic.name);
+        Self {
+            group,
+            topic: topic.name.clone(),
+            id,
+            receiver,
+            rebalances,
+            claimed: None,
+        }
+    }
+
+    async fn next_record(&mut self, topic: &Topic) -> io::Result<Record> {
+        loop {
+            let offset = match self.claimed {
+                Some(offset) => offset,
+                None => {
+                    let (_, next_offset) = topic.offsets().await;
+                    match self.group.claim(self.id, next_offset) {
+                        Some(offset) => {
+                            self.claimed = Some(offset);
+                            offset
+                        }
+                        None => {
+                            tokio::select! {
+                                _ = self.receiver.recv() => {}
+                                _ = self.rebalances.changed() => {}
+                            }
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            match topic.read(offset).await? {
+                Some(record) => return Ok(record),
+                None => {
+                    // the message is no longer stored in the topic log
+                    self.group.skip(vec![offset]).await?;
+                    self.claimed = None;
+                }
+            }
+        }
+    }
+
+    fn delivered(&mut self, offset: u64) {
+        self.group.delivered(self.id, offset);
+        self.claimed = None;
+    }
+}
+
+impl Drop for GroupMember {
+    fn drop(&mut self) {
+        self.group.leave(&self.topic, self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::acl::{AclConfig, AclDefault, AclRule};
+    use crate::server::client::server_broker::ServerBroker;
+    use crate::server::client::Client;
+    use crate::server::config::ServerConfig;
+    use crate::server::service_message::Role;
+    use tokio::io::{duplex, AsyncBufReadExt, AsyncWriteExt, DuplexStream};
+    use tokio::task::JoinHandle;
+
+    /// Connects a client with `connection_message` over an in-memory stream.
+    /// Returns the client served by the broker in the background and the client end of the stream
+    async fn connect(
+        server_broker: &ServerBroker,
+        connection_message: &str,
+    ) -> (JoinHandle<io::Result<()>>, BufReader<DuplexStream>) {
+        let (client_end, server_end) = duplex(1024);
+        let mut client_end = BufReader::new(client_end);
+        let mut server_end = BufReader::new(server_end);
+        client_end
+            .write_all(format!("{}\n", connection_message).as_bytes())
+            .await
+            .unwrap();
+        let client = server_broker
+            .new_client(
+                server_broker.next_connection_id(),
+                ClientAddr::Tcp("127.0.0.1:5000".parse().unwrap()),
+                None,
+                &mut server_end,
+            )
+            .await
+            .unwrap();
+        let task = tokio::spawn(async move {
+            match client {
+                Client::Subscriber(mut subscriber) => subscriber.listen(&mut server_end).await,
+                Client::Publisher(mut publisher) => publisher.listen(&mut server_end).await,
+                Client::Admin(_) => unreachable!(),
+            }
+        });
+        (task, client_end)
+    }
+
+    async fn read_line(client_end: &mut BufReader<DuplexStream>) -> String {
+        let mut line = String::new();
+        timeout(Duration::from_secs(1), client_end.read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        line
+    }
+
+    #[tokio::test]
+    async fn test_listen() {
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let (publisher, mut publisher_end) = connect(
+            &server_broker,
+            r#"{"method": "publish", "topic": "events"}"#,
+        )
+        .await;
+        publisher_end.write_all(b"first\n").await.unwrap();
+
+        let (subscriber, mut subscriber_end) = connect(
+            &server_broker,
+            r#"{"method": "subscribe", "topic": "events", "start": "earliest"}"#,
+        )
+        .await;
+        assert_eq!(read_line(&mut subscriber_end).await, "first\n");
+        publisher_end.write_all(b"second\n").await.unwrap();
+        assert_eq!(read_line(&mut subscriber_end).await, "second\n");
+
+        // the subscriber is disconnected when the client closes the stream
+        drop(subscriber_end);
+        assert!(subscriber.await.unwrap().is_ok());
+        drop(publisher_end);
+        assert!(publisher.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_start_after_log_end() {
+        let mut config = ServerConfig::default();
+        config.topics.default.slow_consumer_policy = SlowConsumerPolicy::Block;
+        config.topics.default.channel_capacity = 2;
+        let server_broker = ServerBroker::open(config).await.unwrap();
+        let (_subscriber, mut subscriber_end) = connect(
+            &server_broker,
+            r#"{"method": "subscribe", "topic": "events", "start": {"offset": 1000000}}"#,
+        )
+        .await;
+        let (_publisher, mut publisher_end) = connect(
+            &server_broker,
+            r#"{"method": "publish", "topic": "events", "ack": "message"}"#,
+        )
+        .await;
+
+        // the subscriber starts from the next message and does not block the publisher
+        for (offset, message) in ["first", "second", "third"].iter().enumerate() {
+            publisher_end
+                .write_all(format!("{}\n", message).as_bytes())
+                .await
+                .unwrap();
+            assert_eq!(
+                read_line(&mut publisher_end).await,
+                format!(
+                    "{{\"type\":\"ack\",\"data\":{{\"sequence\":{},\"offset\":{}}}}}\n",
+                    offset, offset
+                )
+            );
+            assert_eq!(
+                read_line(&mut subscriber_end).await,
+                format!("{}\n", message)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_denied_matching_topics() {
+        let acl = |topics: &[&str]| AclConfig {
+            default: AclDefault::Allow,
+            rules: vec![AclRule {
+                principal: "ops".to_string(),
+                topics: topics.iter().map(|topic| topic.to_string()).collect(),
+                permissions: vec![Permission::Subscribe],
+            }],
+        };
+        let config = ServerConfig {
+            acl: acl(&["sensors/secret"]),
+            ..ServerConfig::default()
+        };
+        let reloaded = ServerConfig {
+            acl: acl(&["sensors/secret", "sensors/vault"]),
+            ..ServerConfig::default()
+        };
+        let server_broker = ServerBroker::open(config)
+            .await
+            .unwrap()
+            .with_config_loader(Some(Arc::new(move || Ok(reloaded.clone()))));
+        server_broker
+            .create_topic("sensors/secret", None)
+            .await
+            .unwrap();
+        let (_subscriber, mut subscriber_end) = connect(
+            &server_broker,
+            r#"{"method": "subscribe", "topic": "sensors/#"}"#,
+        )
+        .await;
+        let denied = |topic: &str| {
+            format!(
+                "{{\"type\":\"error\",\"data\":{{\"code\":\"access_denied\",\"message\":\"anonymous client cannot subscribe to topic \\\"{}\\\"\"}}}}\n",
+                topic
+            )
+        };
+        assert_eq!(
+            read_line(&mut subscriber_end).await,
+            denied("sensors/secret")
+        );
+
+        // topics created later are checked by the current ACL
+        server_broker.reload().await.unwrap();
+        server_broker
+            .create_topic("sensors/vault", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            read_line(&mut subscriber_end).await,
+            denied("sensors/vault")
+        );
+        server_broker
+            .create_topic("sensors/kitchen", None)
+            .await
+            .unwrap();
+        let (_publisher, mut publisher_end) = connect(
+            &server_broker,
+            r#"{"method": "publish", "topic": "sensors/kitchen"}"#,
+        )
+        .await;
+        publisher_end.write_all(b"21.5\n").await.unwrap();
+        assert_eq!(read_line(&mut subscriber_end).await, "21.5\n");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_blocked_publisher() {
+        let mut config = ServerConfig::default();
+        config.topics.default.slow_consumer_policy = SlowConsumerPolicy::Block;
+        config.topics.default.channel_capacity = 1;
+        let server_broker = ServerBroker::open(config).await.unwrap();
+        // the subscriber does not read, so it falls behind once the stream is full
+        let (_subscriber, _subscriber_end) = connect(
+            &server_broker,
+            r#"{"method": "subscribe", "topic": "events"}"#,
+        )
+        .await;
+        let (publisher, mut publisher_end) = connect(
+            &server_broker,
+            r#"{"method": "publish", "topic": "events"}"#,
+        )
+        .await;
+        for _ in 0..6 {
+            publisher_end
+                .write_all(format!("{}\n", "m".repeat(399)).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let publisher_id = server_broker
+            .connections()
+            .iter()
+            .find(|connection| connection.role == Role::Publisher)
+            .unwrap()
+            .id;
+        assert!(server_broker.disconnect_client(publisher_id, DisconnectReason::Admin));
+        assert_eq!(
+            read_line(&mut publisher_end).await,
+            "{\"type\":\"disconnected\",\"data\":{\"reason\":\"admin\"}}\n"
+        );
+        assert!(publisher.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let (subscriber, mut subscriber_end) = connect(
+            &server_broker,
+            r#"{"method": "subscribe", "topic": "events"}"#,
+        )
+        .await;
+        let (_publisher, mut publisher_end) = connect(
+            &server_broker,
+            r#"{"method": "publish", "topic": "events", "ack": "message"}"#,
+        )
+        .await;
+        publisher_end.write_all(b"message\n").await.unwrap();
+        assert_eq!(
+            read_line(&mut publisher_end).await,
+            "{\"type\":\"ack\",\"data\":{\"sequence\":0,\"offset\":0}}\n"
+        );
+
+        // messages already published are sent before disconnecting
+        server_broker.shutdown();
+        assert_eq!(read_line(&mut subscriber_end).await, "message\n");
+        assert_eq!(
+            read_line(&mut subscriber_end).await,
+            "{\"type\":\"disconnected\",\"data\":{\"reason\":\"shutdown\"}}\n"
+        );
+        assert!(subscriber.await.unwrap().is_ok());
+    }
 }
@@ -0,0 +1,387 @@
+use crate::server::acl::AclConfig;
+use crate::server::auth::AuthConfig;
+use crate::server::tls::TlsConfig;
+use crate::server::topic::config::TopicsConfig;
+use crate::server::unix::UnixListenerConfig;
+use crate::server::DEFAULT_SHUTDOWN_TIMEOUT;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, fs};
+
+const DEFAULT_PORT: u16 = 27727;
+const DEFAULT_MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Loads the configuration of the running `Server` when it is reloaded,
+/// see [Server::with_config_loader](crate::server::Server::with_config_loader)
+pub type ConfigLoader = Arc<dyn Fn() -> io::Result<ServerConfig> + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Configuration of the `Server`, loaded from a TOML file
+///
+/// All fields are optional:
+///
+/// ```TOML
+/// log_level = "info"
+/// data_dir = "./data"
+/// shutdown_timeout = 10
+/// topic_name_pattern = "^[a-zA-Z0-9_./-]{3,64}$"
+/// auto_create = true
+///
+/// http_listener = "127.0.0.1:9100"
+///
+/// [listener]
+/// address = "127.0.0.1"
+/// port = 27727
+///
+/// [unix_listener]
+/// path = "/run/simple-kafka/simple-kafka.sock"
+/// mode = 0o660
+///
+/// [tls]
+/// cert_file = "./server.pem"
+/// key_file = "./server.key"
+///
+/// [limits]
+/// max_connections = 1000
+/// max_frame_size = 1048576
+///
+/// [auth]
+/// users_file = "./users.toml"
+///
+/// [acl]
+/// default = "deny"
+///
+/// [[acl.rules]]
+/// principal = "billing-service"
+/// topics = ["billing.*"]
+/// permissions = ["publish", "subscribe"]
+///
+/// [default]
+/// channel_capacity = 32
+///
+/// [topics.telemetry]
+/// channel_capacity = 1024
+/// max_message_size = 4096
+/// ```
+///
+/// - `log_level` is `"off"`, `"error"`, `"warn"`, `"info"`, `"debug"` or `"trace"`
+/// - `data_dir` is the directory where topic logs are stored. Without it topics are kept in memory
+/// - `shutdown_timeout` is how long (in seconds) clients are served after the shutdown is started
+/// - `listener` is the [ListenerConfig](ListenerConfig) of clients
+/// - `unix_listener` is the [UnixListenerConfig](UnixListenerConfig) of clients on the same host.
+///   Without it the `Server` does not listen on a Unix domain socket
+/// - `tls` is the [TlsConfig](TlsConfig) of client connections. Without it connections are not encrypted
+/// - `http_listener` is the address of the HTTP endpoint with health checks and metrics. Without it the endpoint is disabled
+/// - `limits` are the [Limits](Limits) of the `Server`
+/// - `auth` is the [AuthConfig](AuthConfig) of clients
+/// - `acl` is the [AclConfig](AclConfig) of topics
+/// - `default`, `topics`, `topic_name_pattern` and `auto_create` are the [TopicsConfig](TopicsConfig) of the `Server`
+pub struct ServerConfig {
+    pub listener: ListenerConfig,
+    pub unix_listener: Option<UnixListenerConfig>,
+    pub tls: Option<TlsConfig>,
+    pub http_listener: Option<SocketAddr>,
+    pub log_level: LevelFilter,
+    pub data_dir: Option<PathBuf>,
+    #[serde(with = "seconds")]
+    pub shutdown_timeout: Duration,
+    pub limits: Limits,
+    pub auth: AuthConfig,
+    pub acl: AclConfig,
+    #[serde(flatten)]
+    pub topics: TopicsConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Address on which the `Server` accepts clients, `127.0.0.1:27727` by default.
+/// With `enabled = false` the `Server` accepts clients only on the Unix domain socket of `unix_listener`
+pub struct ListenerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Limits of the `Server`. Without them only messages bigger than 1 MiB are rejected
+pub struct Limits {
+    /// Maximal number of simultaneously connected clients. Other clients are rejected
+    pub max_connections: Option<usize>,
+    /// Maximal size (in bytes) of a message received from a client, including the connection message.
+    /// Clients sending bigger messages are disconnected before the message is read
+    pub max_frame_size: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listener: ListenerConfig::default(),
+            unix_listener: None,
+            tls: None,
+            http_listener: None,
+            log_level: LevelFilter::Info,
+            data_dir: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            limits: Limits::default(),
+            auth: AuthConfig::default(),
+            acl: AclConfig::default(),
+            topics: TopicsConfig::default(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: DEFAULT_PORT,
+            enabled: true,
+        }
+    }
+}
+
+impl fmt::Display for ListenerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.enabled {
+            true => write!(f, "{}", self.socket_address()),
+            false => write!(f, "disabled"),
+        }
+    }
+}
+
+impl ListenerConfig {
+    /// Socket address of the listener
+    pub fn socket_address(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+impl ServerConfig {
+    /// Loads configuration from TOML file `path`
+    ///
+    /// # Errors
+    /// - All errors are inherited from [fs::read_to_string]
+    /// - If the file is not valid, function will return [io::ErrorKind::InvalidData]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let config = fs::read_to_string(path)?;
+        let config: Self =
+            toml::from_str(&config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        config
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(config)
+    }
+
+    /// Checks that all values can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.limits.max_connections == Some(0) {
+            return Err("limits: max_connections must be positive".to_string());
+        }
+        if self.limits.max_frame_size == 0 {
+            return Err("limits: max_frame_size must be positive".to_string());
+        }
+        if let Some(unix_listener) = &self.unix_listener {
+            unix_listener.validate()?;
+        } else if !self.listener.enabled {
+            return Err("listener: disabled without unix_listener".to_string());
+        }
+        if let Some(tls) = &self.tls {
+            tls.validate()?;
+        }
+        self.auth.validate()?;
+        self.acl.validate()?;
+        self.topics.validate()
+    }
+}
+
+/// [Duration] in seconds, fractional values are allowed
+mod seconds {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let seconds = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(seconds).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::acl::{AclDefault, AclRule, Permission};
+    use crate::server::topic::config::TopicConfig;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            toml::from_str::<ServerConfig>("").unwrap(),
+            ServerConfig::default()
+        );
+
+        let config = toml::from_str::<ServerConfig>(
+            r#"
+            log_level = "debug"
+            data_dir = "./data"
+            topic_name_pattern = ".*"
+            auto_create = false
+            http_listener = "0.0.0.0:9100"
+
+            [listener]
+            port = 7000
+
+            [unix_listener]
+            path = "/run/simple-kafka.sock"
+            mode = 0o660
+
+            [tls]
+            cert_file = "./server.pem"
+            key_file = "./server.key"
+
+            [limits]
+            max_connections = 2
+            max_frame_size = 4096
+
+            [auth.tokens]
+            ci = "abc"
+
+            [acl]
+            default = "deny"
+
+            [[acl.rules]]
+            principal = "ci"
+            topics = ["telemetry"]
+            permissions = ["publish"]
+
+            [topics.telemetry]
+            max_message_size = 4096
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.log_level, LevelFilter::Debug);
+        assert_eq!(config.data_dir, Some(PathBuf::from("./data")));
+        assert_eq!(
+            config.listener.socket_address(),
+            "127.0.0.1:7000".parse().unwrap()
+        );
+        assert!(config.listener.enabled);
+        assert_eq!(
+            config.unix_listener,
+            Some(UnixListenerConfig {
+                path: PathBuf::from("/run/simple-kafka.sock"),
+                mode: Some(0o660),
+            })
+        );
+        assert_eq!(config.http_listener, Some("0.0.0.0:9100".parse().unwrap()));
+        assert_eq!(
+            config.tls,
+            Some(TlsConfig {
+                cert_file: PathBuf::from("./server.pem"),
+                key_file: PathBuf::from("./server.key"),
+                client_ca_file: None,
+                require_client_cert: false,
+            })
+        );
+        assert_eq!(config.limits.max_connections, Some(2));
+        assert_eq!(config.limits.max_frame_size, 4096);
+        assert_eq!(config.auth.tokens["ci"], "abc");
+        assert!(!config.auth.allow_anonymous);
+        assert_eq!(config.acl.default, AclDefault::Deny);
+        assert_eq!(
+            config.acl.rules,
+            vec![AclRule {
+                principal: "ci".to_string(),
+                topics: vec!["telemetry".to_string()],
+                permissions: vec![Permission::Publish],
+            }]
+        );
+        assert_eq!(config.shutdown_timeout, DEFAULT_SHUTDOWN_TIMEOUT);
+        assert!(!config.topics.auto_create);
+        assert_eq!(config.topics.topic_name_pattern, Some(".*".to_string()));
+        assert_eq!(
+            config.topics.topics["telemetry"],
+            TopicConfig {
+                max_message_size: Some(4096),
+                ..TopicConfig::default()
+            }
+        );
+
+        assert_eq!(
+            toml::from_str::<ServerConfig>("shutdown_timeout = 0.5")
+                .unwrap()
+                .shutdown_timeout,
+            Duration::from_millis(500)
+        );
+        assert!(toml::from_str::<ServerConfig>("shutdown_timeout = -1").is_err());
+        assert!(toml::from_str::<ServerConfig>(r#"log_level = "loud""#).is_err());
+        assert!(toml::from_str::<ServerConfig>("[listener]\nport = 70000").is_err());
+        assert!(toml::from_str::<ServerConfig>("[tls]\ncert_file = \"./server.pem\"").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ServerConfig::default().validate().is_ok());
+
+        let config = toml::from_str::<ServerConfig>("[limits]\nmax_connections = 0").unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>("[limits]\nmax_frame_size = 0").unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>("[listener]\nenabled = false").unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>(
+            "[listener]\nenabled = false\n[unix_listener]\npath = \"./kafka.sock\"",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let config = toml::from_str::<ServerConfig>(
+            "[unix_listener]\npath = \"./kafka.sock\"\nmode = 0o4777",
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>(
+            "[tls]\ncert_file = \"a\"\nkey_file = \"b\"\nrequire_client_cert = true",
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>("[auth.tokens]\nci = \"\"").unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>(
+            "[[acl.rules]]\nprincipal = \"ci\"\npermissions = [\"publish\"]",
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config = toml::from_str::<ServerConfig>("[topics.x]").unwrap();
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,4 +1,7 @@
+use crate::server::auth::Credentials;
 pub use crate::server::client::ClientType;
+use crate::server::framing::Framing;
+use crate::server::topic::config::TopicConfig;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -9,23 +12,123 @@ use serde::{Deserialize, Serialize};
 /// The first message must be provided in JSON format in the following format:
 ///
 /// ```JSON
-/// {"method": "<client_type>", "topic": "<topic_name>"}
+/// {"method": "<client_type>", "topic": "<topic_name>", "topics": ["<topic_name>", ...], "start": <start_position>, "group": "<group_name>", "config": <topic_config>, "framing": <framing>, "format": <message_format>, "ack": <ack_mode>, "name": "<client_name>", "presence": <presence>, "auth": <credentials>}
 /// ```
 ///
 /// - Where `<client_type>` is the serialized [ClientType](ClientType)
-/// - `<topic_name>` - a string describing which topic the client is connecting to
+/// - `<topic_name>` - a string describing which topic the client is connecting to.
+///   A client can connect to several topics listed in `topics`, at least one topic must be given.
+///   A publisher sends messages to the first topic unless another one is chosen in the message,
+///   so a publisher with several topics must use [MessageFormat::Json]
+/// - `<start_position>` - optional serialized [StartPosition](StartPosition), used only by subscribers
+/// - `<group_name>` - optional consumer group of the subscriber. Messages of the topic are distributed among
+///   subscribers of the same group. `<start_position>` is used only when the group is created
+/// - `<topic_config>` - optional serialized [TopicConfig](TopicConfig), used only when the topic is created.
+///   Only clients allowed to manage the `Server` may send it
+/// - `<framing>` - optional serialized [Framing](Framing) of all following messages of the connection
+/// - `<message_format>` - optional serialized [MessageFormat](MessageFormat) of published/delivered messages
+/// - `<ack_mode>` - optional serialized [AckMode](AckMode), used only by publishers
+/// - `<client_name>` - optional name of the client, unique among connected clients.
+///   Subscribers receive it as the name of the publisher of the message
+/// - `<presence>` - optional `true` to receive the clients connected to the topics of the client and
+///   presence events when other clients connect or disconnect
+/// - `<credentials>` - serialized [Credentials](Credentials) of the client,
+///   required if the `Server` authenticates clients
 ///
 /// It is currently not possible to change the settings that were described in the first message
 pub struct ConnectionMessage {
     #[serde(rename = "method")]
     pub client_type: ClientType,
-    pub topic: String,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    #[serde(default)]
+    pub start: StartPosition,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub config: Option<TopicConfig>,
+    #[serde(default)]
+    pub framing: Framing,
+    #[serde(default)]
+    pub format: MessageFormat,
+    #[serde(default)]
+    pub ack: AckMode,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub presence: bool,
+    #[serde(default)]
+    pub auth: Option<Credentials>,
+}
+
+impl ConnectionMessage {
+    /// All topics the client is connecting to, without duplicates: `topic` followed by `topics`
+    pub fn topic_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for name in self.topic.iter().chain(self.topics.iter()) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+/// The offset from which a `Subscriber` starts receiving messages of the topic
+///
+/// In serialized format it is `"earliest"`, `"latest"` or `{"offset": <offset>}`
+pub enum StartPosition {
+    /// From the oldest message stored in the topic log
+    Earliest,
+    /// Only messages published after connection
+    #[default]
+    Latest,
+    /// From the message with the given offset.
+    /// If it is no longer stored, from the oldest stored message, if it is not published yet, from the next message
+    Offset(u64),
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+/// Format of the messages published or delivered to the client
+///
+/// In serialized format it is `"raw"` or `"json"`
+pub enum MessageFormat {
+    /// Messages are published and delivered as is.
+    /// Raw messages have no key and headers, they can be set only by publishers with [MessageFormat::Json]
+    #[default]
+    Raw,
+    /// Publishers send [PublisherMessages](crate::server::publisher_message::PublisherMessage) with an optional key and headers,
+    /// subscribers receive [ServiceMessage::Message](crate::server::service_message::ServiceMessage::Message)
+    /// with the message text and who sent it, from which topic and when
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+/// How the `Server` acknowledges messages of a `Publisher`
+///
+/// In serialized format it is `"none"`, `"message"` or `"batch"`
+pub enum AckMode {
+    /// Nothing is sent back to the publisher
+    #[default]
+    None,
+    /// [ServiceMessage::Ack](crate::server::service_message::ServiceMessage::Ack) is sent after each message
+    Message,
+    /// [ServiceMessage::BatchAck](crate::server::service_message::ServiceMessage::BatchAck) is sent
+    /// after all messages received at once are handled
+    Batch,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::server::client::ClientType::{Publisher, Subscriber};
+    use crate::server::connection_message::StartPosition::{Earliest, Latest, Offset};
 
     #[test]
     fn test_format() {
@@ -36,7 +139,17 @@ mod tests {
             .unwrap(),
             ConnectionMessage {
                 client_type: Subscriber,
-                topic: "Topic".to_string()
+                topic: Some("Topic".to_string()),
+                topics: vec![],
+                start: Latest,
+                group: None,
+                config: None,
+                framing: Framing::Line,
+                format: MessageFormat::Raw,
+                ack: AckMode::None,
+                name: None,
+                presence: false,
+                auth: None,
             }
         );
 
@@ -45,7 +158,17 @@ mod tests {
                 .unwrap(),
             ConnectionMessage {
                 client_type: Publisher,
-                topic: "".to_string()
+                topic: Some("".to_string()),
+                topics: vec![],
+                start: Latest,
+                group: None,
+                config: None,
+                framing: Framing::Line,
+                format: MessageFormat::Raw,
+                ack: AckMode::None,
+                name: None,
+                presence: false,
+                auth: None,
             }
         );
 
@@ -56,8 +179,171 @@ mod tests {
             .unwrap(),
             ConnectionMessage {
                 client_type: Publisher,
-                topic: "".to_string()
+                topic: Some("".to_string()),
+                topics: vec![],
+                start: Latest,
+                group: None,
+                config: None,
+                framing: Framing::Line,
+                format: MessageFormat::Raw,
+                ack: AckMode::None,
+                name: None,
+                presence: false,
+                auth: None,
             }
         );
     }
+
+    #[test]
+    fn test_auth() {
+        let connection_message = serde_json::from_str::<ConnectionMessage>(
+            r#"{"method": "publish", "topic": "t", "auth": {"username": "alice", "password": "secret"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            connection_message.auth,
+            Some(Credentials::Password {
+                username: "alice".to_string(),
+                password: "secret".to_string()
+            })
+        );
+        assert!(!format!("{:?}", connection_message).contains("secret"));
+    }
+
+    #[test]
+    fn test_topics() {
+        let connection_message = serde_json::from_str::<ConnectionMessage>(
+            r#"{"method": "subscribe", "topic": "first", "topics": ["second", "first", "third"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            connection_message.topic_names(),
+            vec![
+                "first".to_string(),
+                "second".to_string(),
+                "third".to_string()
+            ]
+        );
+
+        let connection_message = serde_json::from_str::<ConnectionMessage>(
+            r#"{"method": "subscribe", "topics": ["second"]}"#,
+        )
+        .unwrap();
+        assert_eq!(connection_message.topic_names(), vec!["second".to_string()]);
+
+        let connection_message =
+            serde_json::from_str::<ConnectionMessage>(r#"{"method": "subscribe"}"#).unwrap();
+        assert!(connection_message.topic_names().is_empty());
+    }
+
+    #[test]
+    fn test_start_position() {
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "subscribe", "topic": "Topic", "start": "earliest"}"#
+            )
+            .unwrap()
+            .start,
+            Earliest
+        );
+
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "subscribe", "topic": "Topic", "start": "latest"}"#
+            )
+            .unwrap()
+            .start,
+            Latest
+        );
+
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "subscribe", "topic": "Topic", "start": {"offset": 42}}"#
+            )
+            .unwrap()
+            .start,
+            Offset(42)
+        );
+
+        assert!(serde_json::from_str::<ConnectionMessage>(
+            r#"{"method": "subscribe", "topic": "Topic", "start": "middle"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "publish", "topic": "Topic", "framing": "length_prefixed"}"#
+            )
+            .unwrap()
+            .framing,
+            Framing::LengthPrefixed
+        );
+
+        assert!(serde_json::from_str::<ConnectionMessage>(
+            r#"{"method": "publish", "topic": "Topic", "framing": "chunked"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_message_format() {
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "subscribe", "topic": "Topic", "format": "json"}"#
+            )
+            .unwrap()
+            .format,
+            MessageFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_ack_mode() {
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "publish", "topic": "Topic", "ack": "batch"}"#
+            )
+            .unwrap()
+            .ack,
+            AckMode::Batch
+        );
+    }
+
+    #[test]
+    fn test_group() {
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "subscribe", "topic": "Topic", "group": "workers"}"#
+            )
+            .unwrap()
+            .group,
+            Some("workers".to_string())
+        );
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "publish", "topic": "Topic", "name": "sensor"}"#
+            )
+            .unwrap()
+            .name,
+            Some("sensor".to_string())
+        );
+    }
+
+    #[test]
+    fn test_presence() {
+        assert!(
+            serde_json::from_str::<ConnectionMessage>(
+                r#"{"method": "subscribe", "topic": "Topic", "presence": true}"#
+            )
+            .unwrap()
+            .presence
+        );
+    }
 }
@@ -0,0 +1,266 @@
+use crate::server::client::server_broker::DELIMITER;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::io::{Error, ErrorKind};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+/// How messages are separated in the connection after the connection message
+///
+/// In serialized format it is `"line"` or `"length_prefixed"`
+///
+/// The connection message itself is always a line.
+/// All following messages of the connection (in both directions) use the negotiated framing
+pub enum Framing {
+    /// Each message is a line ending with the delimiter (`\n`). Messages cannot contain the delimiter
+    #[default]
+    Line,
+    /// Each message is a frame: 4 bytes of the payload length (big-endian `u32`) followed by the payload.
+    /// Payload can contain any bytes
+    LengthPrefixed,
+}
+
+impl Framing {
+    /// Reads the next message from `reader` and returns its payload. Returns `None` if the connection is closed
+    ///
+    /// Partially read message is kept in `buffer`, so the function can be safely cancelled
+    /// (e.g. in `tokio::select!`) and called again with the same `buffer`
+    ///
+    /// Payloads bigger than `max_size` are not buffered: a length prefix is checked before the payload is read
+    /// and a line is read only until it exceeds `max_size`
+    ///
+    /// # Errors
+    /// - If the connection is closed in the middle of a message, function will return [ErrorKind::InvalidData]
+    /// - If the payload is bigger than `max_size`, function will return [ErrorKind::InvalidData]
+    /// - All other errors are inherited from [AsyncBufReadExt::fill_buf]
+    pub async fn read_frame<R: AsyncBufRead + Unpin>(
+        &self,
+        reader: &mut R,
+        buffer: &mut Vec<u8>,
+        max_size: usize,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let too_large = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("message is bigger than {} bytes", max_size),
+            )
+        };
+        loop {
+            if let Some(frame) = self.take_frame(buffer) {
+                return match frame.len() > max_size {
+                    true => Err(too_large()),
+                    false => Ok(Some(frame)),
+                };
+            }
+            if self.exceeds(buffer, max_size) {
+                return Err(too_large());
+            }
+
+            let available = reader.fill_buf().await?;
+            if available.is_empty() {
+                return match buffer.is_empty() {
+                    true => Ok(None),
+                    false => Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "connection closed in the middle of a message",
+                    )),
+                };
+            }
+
+            let consumed = match self {
+                Framing::Line => match available.iter().position(|byte| *byte == DELIMITER) {
+                    Some(position) => position + 1,
+                    None => available.len(),
+                },
+                Framing::LengthPrefixed => available.len().min(self.missing(buffer)),
+            };
+            buffer.extend_from_slice(&available[..consumed]);
+            reader.consume(consumed);
+        }
+    }
+
+    /// Whether the message partially read into `buffer` is bigger than `max_size`
+    fn exceeds(&self, buffer: &[u8], max_size: usize) -> bool {
+        match self {
+            Framing::Line => buffer.len() > max_size,
+            Framing::LengthPrefixed => {
+                Self::frame_length(buffer).is_some_and(|length| length > max_size)
+            }
+        }
+    }
+
+    /// Number of bytes `buffer` lacks to contain a whole length-prefixed frame
+    fn missing(&self, buffer: &[u8]) -> usize {
+        match Self::frame_length(buffer) {
+            Some(length) => LENGTH_PREFIX_SIZE + length - buffer.len(),
+            None => LENGTH_PREFIX_SIZE - buffer.len(),
+        }
+    }
+
+    fn frame_length(buffer: &[u8]) -> Option<usize> {
+        let prefix: [u8; LENGTH_PREFIX_SIZE] = buffer.get(..LENGTH_PREFIX_SIZE)?.try_into().ok()?;
+        Some(u32::from_be_bytes(prefix) as usize)
+    }
+
+    /// Removes the whole message from `buffer` and returns its payload
+    fn take_frame(&self, buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+        match self {
+            Framing::Line if buffer.last() == Some(&DELIMITER) => {
+                buffer.pop();
+                Some(std::mem::take(buffer))
+            }
+            Framing::LengthPrefixed => match Self::frame_length(buffer) {
+                Some(length) if buffer.len() == LENGTH_PREFIX_SIZE + length => {
+                    let frame = buffer.split_off(LENGTH_PREFIX_SIZE);
+                    buffer.clear();
+                    Some(frame)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Encodes `payload` as a message to be sent to the client
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(payload.len() + LENGTH_PREFIX_SIZE);
+        match self {
+            Framing::Line => {
+                frame.extend_from_slice(payload);
+                frame.push(DELIMITER);
+            }
+            Framing::LengthPrefixed => {
+                frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+                frame.extend_from_slice(payload);
+            }
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_line() {
+        let mut reader: &[u8] = b"first\nsecond\n\nthird";
+        let mut buffer = vec![];
+        let framing = Framing::Line;
+
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap(),
+            Some(b"first".to_vec())
+        );
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap(),
+            Some(b"second".to_vec())
+        );
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap(),
+            Some(b"".to_vec())
+        );
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+
+        assert_eq!(framing.encode(b"message"), b"message\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_length_prefixed() {
+        let framing = Framing::LengthPrefixed;
+        let mut data = framing.encode(b"binary\n\0payload");
+        data.extend(framing.encode(b""));
+        assert_eq!(&data[..4], &[0, 0, 0, 15]);
+
+        let mut reader: &[u8] = &data;
+        let mut buffer = vec![];
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap(),
+            Some(b"binary\n\0payload".to_vec())
+        );
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap(),
+            Some(vec![])
+        );
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let mut reader: &[u8] = &[0, 0, 0, 5, b'a'];
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 64)
+                .await
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_size() {
+        let mut buffer = vec![];
+        let mut reader: &[u8] = b"small
+large message
+";
+        let framing = Framing::Line;
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 5)
+                .await
+                .unwrap(),
+            Some(b"small".to_vec())
+        );
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 5)
+                .await
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+
+        // the payload is not read after the length prefix
+        let mut buffer = vec![];
+        let mut reader: &[u8] = &[255, 255, 255, 255, b'a'];
+        let framing = Framing::LengthPrefixed;
+        assert_eq!(
+            framing
+                .read_frame(&mut reader, &mut buffer, 5)
+                .await
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(buffer.len(), 4);
+    }
+}
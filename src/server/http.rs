@@ -0,0 +1,204 @@
+use crate::server::client::server_broker::ServerBroker;
+use crate::server::shutdown::ShutdownHandle;
+use log::{debug, warn};
+use std::io;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+/// Maximal size of the request line and headers of a request
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// How long the endpoint waits for the request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Response of the HTTP endpoint
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+        .into_bytes()
+    }
+}
+
+/// State of the `Server` seen by the HTTP endpoint
+#[derive(Clone)]
+pub(crate) struct ServerState {
+    /// Set when stored topics are recovered and the `Server` accepts clients
+    pub server_broker: Arc<OnceLock<Arc<ServerBroker>>>,
+    pub shutdown: ShutdownHandle,
+}
+
+impl ServerState {
+    /// Returns `true` if the `Server` is ready to serve clients
+    fn is_ready(&self) -> bool {
+        self.server_broker.get().is_some() && !self.shutdown.is_shutdown()
+    }
+}
+
+/// Serves HTTP requests on `listener` until the task is aborted:
+/// - `GET /healthz` returns `200 OK` while the `Server` is running
+/// - `GET /readyz` returns `200 OK` when the `Server` is ready to serve clients: the listener is bound
+///   and stored topics are recovered. It returns `503 Service Unavailable` before that and after the shutdown is started
+/// - `GET /metrics` returns metrics of the `Server` in the Prometheus text format
+///
+/// Each connection serves a single request
+pub(crate) async fn serve(listener: Arc<TcpListener>, state: ServerState) {
+    // requests being served are aborted together with the endpoint
+    let mut requests = JoinSet::new();
+    loop {
+        let (stream, socket_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept HTTP connection: {}", e);
+                continue;
+            }
+        };
+        while requests.try_join_next().is_some() {}
+        let state = state.clone();
+        requests.spawn(async move {
+            if let Err(e) = handle(stream, socket_addr, &state).await {
+                debug!("Failed to serve HTTP request from {}: {}", socket_addr, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    socket_addr: SocketAddr,
+    state: &ServerState,
+) -> io::Result<()> {
+    let response = match timeout(REQUEST_TIMEOUT, read_request_head(&mut stream)).await {
+        Ok(Ok(head)) => {
+            let mut parts = head.split_whitespace();
+            let method = parts.next().unwrap_or_default();
+            let path = parts.next().unwrap_or_default();
+            debug!("HTTP request from {}: {} {}", socket_addr, method, path);
+            route(method, path, state).await
+        }
+        Ok(Err(e)) if e.kind() == ErrorKind::InvalidData => {
+            Response::text("400 Bad Request", "bad request\n")
+        }
+        Ok(Err(e)) => return Err(e),
+        Err(_) => Response::text("408 Request Timeout", "request timeout\n"),
+    };
+    stream.write_all(&response.to_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn route(method: &str, path: &str, state: &ServerState) -> Response {
+    // the query string is ignored
+    let path = path.split('?').next().unwrap_or_default();
+    if !matches!(path, "/healthz" | "/readyz" | "/metrics") {
+        return Response::text("404 Not Found", "not found\n");
+    }
+    if method != "GET" {
+        return Response::text("405 Method Not Allowed", "method not allowed\n");
+    }
+    match (path, state.server_broker.get()) {
+        ("/healthz", _) => Response::text("200 OK", "ok\n"),
+        ("/readyz", _) if state.is_ready() => Response::text("200 OK", "ready\n"),
+        ("/metrics", Some(server_broker)) => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: server_broker.metrics().await,
+        },
+        _ => Response::text("503 Service Unavailable", "not ready\n"),
+    }
+}
+
+/// Reads the request line and headers of the request. The request line is returned
+///
+/// # Errors
+/// - If the request is too large or the connection is closed before the end of headers,
+///   function will return [ErrorKind::InvalidData]
+/// - All other errors are inherited from [TcpStream::read]
+async fn read_request_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "request is too large",
+            ));
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "connection closed before the end of headers",
+            ));
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    Ok(head.lines().next().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::ServerConfig;
+
+    #[tokio::test]
+    async fn test_route() {
+        let state = ServerState {
+            server_broker: Arc::default(),
+            shutdown: ShutdownHandle::default(),
+        };
+        let status = |response: Response| response.status;
+
+        // stored topics are not recovered yet
+        assert_eq!(status(route("GET", "/healthz", &state).await), "200 OK");
+        assert_eq!(
+            status(route("GET", "/readyz", &state).await),
+            "503 Service Unavailable"
+        );
+        assert_eq!(
+            status(route("GET", "/metrics", &state).await),
+            "503 Service Unavailable"
+        );
+
+        let server_broker = ServerBroker::open(ServerConfig::default()).await.unwrap();
+        let _ = state.server_broker.set(Arc::new(server_broker));
+        assert_eq!(status(route("GET", "/readyz", &state).await), "200 OK");
+        assert_eq!(status(route("GET", "/metrics", &state).await), "200 OK");
+        assert_eq!(
+            status(route("POST", "/readyz", &state).await),
+            "405 Method Not Allowed"
+        );
+        assert_eq!(status(route("GET", "/", &state).await), "404 Not Found");
+
+        state.shutdown.shutdown();
+        assert_eq!(status(route("GET", "/healthz", &state).await), "200 OK");
+        assert_eq!(
+            status(route("GET", "/readyz", &state).await),
+            "503 Service Unavailable"
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::server::client::ClientConnectionError;
+use crate::server::service_message::Role;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Selects one of the counters of [TopicMetrics]
+type TopicCounter = fn(&TopicMetrics) -> &AtomicU64;
+
+/// Counters of a topic, exported by the metrics endpoint
+#[derive(Default)]
+pub(crate) struct TopicMetrics {
+    messages_in: AtomicU64,
+    bytes_in: AtomicU64,
+    messages_out: AtomicU64,
+    bytes_out: AtomicU64,
+    lagged_receivers: AtomicU64,
+}
+
+impl TopicMetrics {
+    /// A message of `size` bytes is published to the topic
+    pub fn published(&self, size: usize) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    /// A message of `size` bytes is sent to a subscriber of the topic
+    pub fn delivered(&self, size: usize) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    /// A subscriber of the topic fell behind the messages published in real time
+    pub fn lagged(&self) {
+        self.lagged_receivers.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counters of the `Server`, exported by the metrics endpoint
+#[derive(Default)]
+pub(crate) struct ServerMetrics {
+    connections: AtomicU64,
+    rejected_connections: AtomicU64,
+    connection_errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl ServerMetrics {
+    /// A connection is accepted by the `Server`
+    pub fn connection_accepted(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A connection is rejected because of the limits of the `Server`
+    pub fn connection_rejected(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A client failed to connect with `error`
+    pub fn connection_error(&self, error: &ClientConnectionError) {
+        *self
+            .connection_errors
+            .lock()
+            .unwrap()
+            .entry(error.variant_name())
+            .or_default() += 1;
+    }
+}
+
+impl ClientConnectionError {
+    /// Name of the variant, used as a metric label
+    fn variant_name(&self) -> &'static str {
+        match self {
+            ClientConnectionError::UnexpectedMessage(_) => "unexpected_message",
+            ClientConnectionError::Aborted => "aborted",
+            ClientConnectionError::TimedOut => "timed_out",
+            ClientConnectionError::ReaderError => "reader_error",
+            ClientConnectionError::StorageError(_) => "storage_error",
+            ClientConnectionError::UnknownTopic(_) => "unknown_topic",
+            ClientConnectionError::InvalidTopicName(_) => "invalid_topic_name",
+            ClientConnectionError::DuplicateName(_) => "duplicate_name",
+            ClientConnectionError::AuthenticationFailed(_) => "authentication_failed",
+            ClientConnectionError::AccessDenied(_) => "access_denied",
+        }
+    }
+}
+
+/// Formats metrics in the Prometheus text exposition format
+pub(crate) struct MetricsWriter {
+    output: String,
+}
+
+impl MetricsWriter {
+    pub fn new() -> Self {
+        Self {
+            output: String::new(),
+        }
+    }
+
+    /// Writes metric family `name` with one sample per label value
+    fn family<'a>(
+        &mut self,
+        name: &str,
+        kind: &str,
+        help: &str,
+        label: &str,
+        samples: impl IntoIterator<Item = (&'a str, u64)>,
+    ) {
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+        for (label_value, value) in samples {
+            let _ = writeln!(
+                self.output,
+                r#"{}{{{}="{}"}} {}"#,
+                name,
+                label,
+                escape(label_value),
+                value
+            );
+        }
+    }
+
+    /// Writes metric family `name` with a single sample
+    fn single(&mut self, name: &str, kind: &str, help: &str, value: u64) {
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+        let _ = writeln!(self.output, "{} {}", name, value);
+    }
+
+    /// Writes counters of `topics`
+    pub fn topics(&mut self, topics: &[(String, &TopicMetrics)]) {
+        let counters: [(&str, &str, TopicCounter); 5] = [
+            (
+                "simple_kafka_messages_in_total",
+                "Messages published to the topic",
+                |metrics| &metrics.messages_in,
+            ),
+            (
+                "simple_kafka_bytes_in_total",
+                "Bytes of messages published to the topic",
+                |metrics| &metrics.bytes_in,
+            ),
+            (
+                "simple_kafka_messages_out_total",
+                "Messages sent to subscribers of the topic",
+                |metrics| &metrics.messages_out,
+            ),
+            (
+                "simple_kafka_bytes_out_total",
+                "Bytes of messages sent to subscribers of the topic",
+                |metrics| &metrics.bytes_out,
+            ),
+            (
+                "simple_kafka_lagged_receivers_total",
+                "Times subscribers of the topic fell behind the messages published in real time",
+                |metrics| &metrics.lagged_receivers,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            self.family(
+                name,
+                "counter",
+                help,
+                "topic",
+                topics.iter().map(|(topic, metrics)| {
+                    (topic.as_str(), counter(metrics).load(Ordering::Relaxed))
+                }),
+            );
+        }
+    }
+
+    /// Writes the number of connected clients by role
+    pub fn clients(&mut self, roles: &[Role]) {
+        let count = |role: Role| roles.iter().filter(|r| **r == role).count() as u64;
+        self.family(
+            "simple_kafka_connected_clients",
+            "gauge",
+            "Clients connected to the server",
+            "role",
+            [
+                ("publisher", count(Role::Publisher)),
+                ("subscriber", count(Role::Subscriber)),
+                ("admin", count(Role::Admin)),
+            ],
+        );
+    }
+
+    /// Writes counters of the `Server`
+    pub fn server(&mut self, metrics: &ServerMetrics) {
+        self.single(
+            "simple_kafka_connections_total",
+            "counter",
+            "Connections accepted by the server",
+            metrics.connections.load(Ordering::Relaxed),
+        );
+        self.single(
+            "simple_kafka_rejected_connections_total",
+            "counter",
+            "Connections rejected because of the limits of the server",
+            metrics.rejected_connections.load(Ordering::Relaxed),
+        );
+        let errors = metrics.connection_errors.lock().unwrap().clone();
+        self.family(
+            "simple_kafka_connection_errors_total",
+            "counter",
+            "Clients failed to connect, by error",
+            "error",
+            errors,
+        );
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Escapes a label value of the Prometheus text format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let topic = TopicMetrics::default();
+        topic.published(10);
+        topic.published(5);
+        topic.delivered(10);
+        let server = ServerMetrics::default();
+        server.connection_accepted();
+        server.connection_error(&ClientConnectionError::Aborted);
+        server.connection_error(&ClientConnectionError::Aborted);
+
+        let mut writer = MetricsWriter::new();
+        writer.topics(&[("a\"b".to_string(), &topic)]);
+        writer.clients(&[Role::Subscriber, Role::Subscriber]);
+        writer.server(&server);
+        let output = writer.finish();
+
+        assert!(output.contains("# TYPE simple_kafka_messages_in_total counter\n"));
+        assert!(output.contains("simple_kafka_messages_in_total{topic=\"a\\\"b\"} 2\n"));
+        assert!(output.contains("simple_kafka_bytes_in_total{topic=\"a\\\"b\"} 15\n"));
+        assert!(output.contains("simple_kafka_messages_out_total{topic=\"a\\\"b\"} 1\n"));
+        assert!(output.contains("simple_kafka_connected_clients{role=\"subscriber\"} 2\n"));
+        assert!(output.contains("simple_kafka_connected_clients{role=\"publisher\"} 0\n"));
+        assert!(output.contains("simple_kafka_connections_total 1\n"));
+        assert!(output.contains("simple_kafka_connection_errors_total{error=\"aborted\"} 2\n"));
+    }
+}
@@ -1,36 +1,112 @@
+pub mod acl;
+pub mod admin_message;
+pub mod auth;
 pub mod client;
+pub mod config;
 pub mod connection_message;
+pub mod framing;
+pub(crate) mod http;
+pub(crate) mod metrics;
+pub mod publisher_message;
+pub mod reload;
+pub mod service_message;
+pub mod shutdown;
+pub mod stream;
+pub mod subscriber_message;
+pub mod tls;
 pub(crate) mod topic;
+pub mod unix;
 
 extern crate tokio;
 
-use log::{error, info};
+use log::{error, info, warn};
+use std::future::pending;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::BufReader;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio_rustls::TlsAcceptor;
 
-use crate::server::client::server_broker::ServerBroker;
+use crate::server::acl::AclConfig;
+use crate::server::auth::{AuthConfig, Authenticator};
+use crate::server::client::server_broker::{ServerBroker, CONNECTION_MESSAGE_TIMEOUT};
 use crate::server::client::{Client, ClientConnectionError};
+use crate::server::config::{ConfigLoader, Limits, ListenerConfig, ServerConfig};
+use crate::server::framing::Framing;
+use crate::server::http::ServerState;
+use crate::server::reload::ReloadHandle;
+use crate::server::service_message::{ErrorCode, ServiceMessage};
+use crate::server::shutdown::ShutdownHandle;
+use crate::server::stream::{write_frame, ClientAddr, ClientStream};
+use crate::server::tls::TlsConfig;
+use crate::server::topic::config::TopicsConfig;
+#[cfg(unix)]
+use crate::server::unix::{UnixListenerConfig, UnixSocketListener};
 use tokio::io;
 use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+/// How long the `Server` waits for clients to be served after the shutdown is started
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many clients rejected by [Limits::max_connections] are notified at once. Other rejected clients are closed at once
+const MAX_PENDING_REJECTIONS: usize = 64;
+/// How long a rejected client is given to receive the error, including the TLS handshake
+const REJECTION_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// `Server` is Async simplified software message broker server
 ///
 /// `Server` listening on `socket_address` with an automatically created `listener: TcpListener`
 ///
-/// To start the `Server` use the [Server::run] method
+/// Messages of each topic are appended to the topic log. With [Server::with_data_dir] logs are stored on disk
+/// and survive `Server` restarts, otherwise they are kept in memory
+///
+/// To start the `Server` use the [Server::run] method, to stop it use [ShutdownHandle::shutdown]
+/// of [Server::shutdown_handle]. The configuration of the running `Server` is reloaded with [ReloadHandle::reload]
+/// of [Server::reload_handle]
+///
+/// Clients are authenticated by users and tokens of [Server::with_auth_config] and authenticators of
+/// [Server::with_authenticator] before they are connected to topics.
+/// Then access to topics is checked against [Server::with_acl_config]
+///
+/// With [Server::with_tls_config] client connections are encrypted with TLS. Clients are served
+/// identically over plain and encrypted connections
+///
+/// With [Server::with_unix_listener] clients running on the same host also connect over a Unix domain socket,
+/// [Server::new_unix] creates a `Server` accepting clients only there. Clients are served identically over TCP
+/// and the socket
+///
+/// With [Server::with_http_listener] the `Server` serves over HTTP:
+/// - `/healthz` - liveness check, `200 OK` while the `Server` is running
+/// - `/readyz` - readiness check, `200 OK` after stored topics are recovered and until the shutdown is started,
+///   `503 Service Unavailable` otherwise
+/// - `/metrics` - metrics of topics and connections in the Prometheus text format
 ///
 /// # Examples
-/// ```
+/// ```no_run
+/// use simple_kafka::Server;
+/// use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+/// use tokio::io;
+///
 /// #[tokio::main]
 /// async fn main() -> io::Result<()> {
-///     let server = Server::new(SocketAddr::new(IpAddr::new("127.0.0.1"), 7000)).await?;
-///     server.run().await?;
+///     let socket_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 7000);
+///     let mut server = Server::new(&socket_address).await?.with_data_dir("data");
+///     server.run().await
 /// }
 /// ```
 pub struct Server {
-    socket_address: SocketAddr,
-    listener: TcpListener,
+    listener: Option<TcpListener>,
+    #[cfg(unix)]
+    unix_listener: Option<UnixSocketListener>,
+    http_listener: Option<Arc<TcpListener>>,
+    tls_acceptor: Option<TlsAcceptor>,
+    config: ServerConfig,
+    config_loader: Option<ConfigLoader>,
+    authenticators: Vec<Arc<dyn Authenticator>>,
+    shutdown: ShutdownHandle,
+    reload: ReloadHandle,
 }
 
 impl Server {
@@ -39,16 +115,216 @@ impl Server {
     /// # Errors
     /// All errors are inherited from [TcpListener::bind]
     pub async fn new(socket_address: &SocketAddr) -> io::Result<Self> {
-        Ok(Self {
-            socket_address: socket_address.clone(),
-            listener: TcpListener::bind(socket_address).await?,
-        })
+        let mut server = Self::without_listeners();
+        server.listener = Some(TcpListener::bind(socket_address).await?);
+        server.config.listener = ListenerConfig {
+            address: socket_address.ip(),
+            port: socket_address.port(),
+            enabled: true,
+        };
+        Ok(server)
+    }
+
+    /// Create and launch new `Server` accepting clients only on the Unix domain socket of `unix_config`
+    ///
+    /// # Errors
+    /// All errors are inherited from [Server::with_unix_listener]
+    #[cfg(unix)]
+    pub async fn new_unix(unix_config: UnixListenerConfig) -> io::Result<Self> {
+        Self::without_listeners()
+            .with_unix_listener(unix_config)
+            .await
+    }
+
+    fn without_listeners() -> Self {
+        Self {
+            listener: None,
+            #[cfg(unix)]
+            unix_listener: None,
+            http_listener: None,
+            tls_acceptor: None,
+            config: ServerConfig {
+                listener: ListenerConfig {
+                    enabled: false,
+                    ..ListenerConfig::default()
+                },
+                ..ServerConfig::default()
+            },
+            config_loader: None,
+            authenticators: vec![],
+            shutdown: ShutdownHandle::default(),
+            reload: ReloadHandle::default(),
+        }
+    }
+
+    /// Create and launch new `Server` according to `config`.
+    /// The log level of `config` is not applied, the logger is set up by the application
+    ///
+    /// # Errors
+    /// - All errors are inherited from [TcpListener::bind]
+    /// - All errors are inherited from [Server::with_unix_listener], on other platforms than Unix
+    ///   a Unix domain socket is [ErrorKind::Unsupported](io::ErrorKind::Unsupported)
+    /// - All errors are inherited from [TlsConfig::acceptor]
+    pub async fn from_config(config: &ServerConfig) -> io::Result<Self> {
+        let mut server = Self::without_listeners();
+        if config.listener.enabled {
+            let socket_address = config.listener.socket_address();
+            let listener = TcpListener::bind(socket_address).await.map_err(|e| {
+                io::Error::new(e.kind(), format!("listener {} -- {}", socket_address, e))
+            })?;
+            server.listener = Some(listener);
+        }
+        if let Some(unix_config) = &config.unix_listener {
+            #[cfg(unix)]
+            {
+                server = server.with_unix_listener(unix_config.clone()).await?;
+            }
+            #[cfg(not(unix))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "socket {} -- Unix domain sockets are not supported",
+                    unix_config.path.display()
+                ),
+            ));
+        }
+        if let Some(http_address) = &config.http_listener {
+            server = server.with_http_listener(http_address).await?;
+        }
+        if let Some(tls_config) = &config.tls {
+            server = server.with_tls_config(tls_config.clone())?;
+        }
+        server.config = config.clone();
+        Ok(server)
+    }
+
+    /// Serves health checks and metrics over HTTP on `http_address`, see [Server]
+    ///
+    /// # Errors
+    /// All errors are inherited from [TcpListener::bind]
+    pub async fn with_http_listener(mut self, http_address: &SocketAddr) -> io::Result<Self> {
+        self.http_listener = Some(Arc::new(TcpListener::bind(http_address).await?));
+        self.config.http_listener = Some(*http_address);
+        Ok(self)
+    }
+
+    /// Accepts clients running on the same host on the Unix domain socket of `unix_config`, see [UnixListenerConfig].
+    /// A stale socket file is removed first, the socket file is removed when the `Server` is dropped.
+    /// Clients without credentials are authenticated as `uid:<uid>` of their process
+    ///
+    /// # Errors
+    /// - If the socket is used by a running `Server`, function will return [ErrorKind::AddrInUse](io::ErrorKind::AddrInUse)
+    /// - If the path exists and is not a socket, function will return [ErrorKind::AlreadyExists](io::ErrorKind::AlreadyExists)
+    /// - All other errors are inherited from [UnixListener::bind](tokio::net::UnixListener::bind) and [std::fs::set_permissions]
+    #[cfg(unix)]
+    pub async fn with_unix_listener(mut self, unix_config: UnixListenerConfig) -> io::Result<Self> {
+        self.unix_listener = Some(unix_config.bind().await?);
+        self.config.unix_listener = Some(unix_config);
+        Ok(self)
+    }
+
+    /// Encrypts client connections with TLS according to `tls_config`.
+    /// Clients with a verified certificate and without credentials are authenticated as the subject of the certificate
+    ///
+    /// # Errors
+    /// All errors are inherited from [TlsConfig::acceptor]
+    pub fn with_tls_config(mut self, tls_config: TlsConfig) -> io::Result<Self> {
+        self.tls_acceptor = Some(tls_config.acceptor()?);
+        self.config.tls = Some(tls_config);
+        Ok(self)
+    }
+
+    /// Sets the directory where topic logs are stored
+    ///
+    /// Topics already stored in `data_dir` are recovered when the `Server` starts
+    pub fn with_data_dir(mut self, data_dir: impl Into<PathBuf>) -> Self {
+        self.config.data_dir = Some(data_dir.into());
+        self
+    }
+
+    /// Sets the default config of new topics and topics declared in advance
+    pub fn with_topics_config(mut self, topics_config: TopicsConfig) -> Self {
+        self.config.topics = topics_config;
+        self
+    }
+
+    /// Sets how long the `Server` waits for clients to be served after the shutdown is started,
+    /// [DEFAULT_SHUTDOWN_TIMEOUT] by default. Clients still connected afterwards are dropped
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.config.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
+    /// Sets the limits of the `Server`
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Sets how clients are authenticated. Without users and tokens clients are not authenticated
+    pub fn with_auth_config(mut self, auth_config: AuthConfig) -> Self {
+        self.config.auth = auth_config;
+        self
+    }
+
+    /// Sets which clients may publish to, subscribe to and manage topics. Without rules everything except managing the `Server` is allowed
+    pub fn with_acl_config(mut self, acl_config: AclConfig) -> Self {
+        self.config.acl = acl_config;
+        self
+    }
+
+    /// Sets how the configuration is loaded when it is reloaded with [ReloadHandle::reload]
+    /// or by an [Admin](crate::server::client::Admin)
+    ///
+    /// Settings of topics, limits, authentication, access control lists, the log level and the shutdown timeout are applied to the running `Server`,
+    /// changes of the listeners, TLS, the data directory and `channel_capacity` of existing topics require a restart
+    pub fn with_config_loader(
+        mut self,
+        config_loader: impl Fn() -> io::Result<ServerConfig> + Send + Sync + 'static,
+    ) -> Self {
+        self.config_loader = Some(Arc::new(config_loader));
+        self
+    }
+
+    /// Adds `authenticator` of clients. It is used together with users and tokens of
+    /// [AuthConfig], credentials are accepted by the first authenticator that
+    /// returns a principal. With any authenticator clients without credentials are rejected, unless
+    /// `allow_anonymous` is set
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticators.push(Arc::new(authenticator));
+        self
+    }
+
+    /// Returns a handle to stop the `Server`
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Returns a handle to reload the configuration of the `Server`
+    pub fn reload_handle(&self) -> ReloadHandle {
+        self.reload.clone()
     }
 
     /// Returns the port on which the server is running.
     /// It is useful if you specify `port=0` when creating a new [Server]
+    ///
+    /// # Panics
+    /// If the `Server` does not listen on TCP
     pub fn port(&self) -> u16 {
-        self.listener.local_addr().unwrap().port()
+        self.listener
+            .as_ref()
+            .expect("the server does not listen on TCP")
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    /// Returns the port of the HTTP endpoint, if any.
+    /// It is useful if you specify `port=0` in [Server::with_http_listener]
+    pub fn http_port(&self) -> Option<u16> {
+        self.http_listener
+            .as_ref()
+            .map(|listener| listener.local_addr().unwrap().port())
     }
 
     /// Run created `Server`
@@ -56,78 +332,309 @@ impl Server {
     /// While the `Server` is running, it listens for new connections of `Publishers`/`Subscribers` and
     /// sending messages from `Publishers` to `Subscribers` inside one topic via [ServerBroker]
     ///
+    /// The function returns when the shutdown is started by [ShutdownHandle::shutdown] and all clients are
+    /// disconnected, or the shutdown timeout has passed
+    ///
     /// # Errors
-    /// All errors are inherited from [TcpListener::accept]
+    /// - If stored topics cannot be recovered, function will return error from [std::fs] operations
+    /// - All other errors are inherited from [TcpListener::accept] and accepting Unix domain socket clients
     pub async fn run(&mut self) -> io::Result<()> {
-        info!("Start kafka server on address {}", self.socket_address);
-        let origin_server_broker = Arc::new(ServerBroker::default());
+        info!("Start kafka server on {}", self.listeners());
+        // the HTTP endpoint is started first to answer health checks while stored topics are recovered.
+        // It is aborted when the `Server` stops
+        let mut http_endpoint = JoinSet::new();
+        let server_state = ServerState {
+            server_broker: Arc::default(),
+            shutdown: self.shutdown.clone(),
+        };
+        if let Some(http_listener) = &self.http_listener {
+            info!(
+                "Start HTTP endpoint on address {}",
+                http_listener.local_addr()?
+            );
+            http_endpoint.spawn(http::serve(http_listener.clone(), server_state.clone()));
+        }
+        let origin_server_broker = Arc::new(
+            ServerBroker::open(self.config.clone())
+                .await?
+                .with_config_loader(self.config_loader.clone())
+                .with_authenticators(self.authenticators.clone()),
+        );
+        let _ = server_state.server_broker.set(origin_server_broker.clone());
+        let mut connections = JoinSet::new();
+        let mut rejections = JoinSet::new();
 
         loop {
+            let (stream, socket_addr) = tokio::select! {
+                accepted = self.accept() => accepted?,
+                _ = self.reload.requested() => {
+                    // the result is logged by the broker
+                    let _ = origin_server_broker.reload().await;
+                    continue;
+                }
+                _ = self.shutdown.wait() => break,
+            };
+            // finished connections are removed, so that the set does not grow
+            while connections.try_join_next().is_some() {}
+            while rejections.try_join_next().is_some() {}
+            if origin_server_broker
+                .limits()
+                .max_connections
+                .is_some_and(|max_connections| connections.len() >= max_connections)
+            {
+                info!(
+                    "Client ({}) rejected, there are already {} connections",
+                    socket_addr,
+                    connections.len()
+                );
+                origin_server_broker.server_metrics().connection_rejected();
+                if rejections.len() >= MAX_PENDING_REJECTIONS {
+                    // the stream is dropped without the error, so that rejected clients cannot pile up
+                    continue;
+                }
+                let message = ServiceMessage::Error {
+                    code: ErrorCode::TooManyConnections,
+                    message: "too many connections".to_string(),
+                };
+                // the rejected client gets the error over TLS too, so the handshake is not awaited here
+                let tls_acceptor = self.tls_acceptor.clone();
+                rejections.spawn(timeout(REJECTION_TIMEOUT, async move {
+                    if let Ok(mut stream) = stream.handshake(tls_acceptor.as_ref()).await {
+                        let _ = write_frame(&mut stream, &message.to_frame(Framing::Line)).await;
+                        let _ = stream.shutdown().await;
+                    }
+                }));
+                continue;
+            }
+            origin_server_broker.server_metrics().connection_accepted();
+
             let server_broker = origin_server_broker.clone();
+            let connection_id = server_broker.next_connection_id();
+            let shutdown = self.shutdown.clone();
+            let tls_acceptor = self.tls_acceptor.clone();
+            connections.spawn(async move {
+                info!(
+                    "Client #{} ({}) connected to server",
+                    connection_id, socket_addr
+                );
+                let mut stream = match stream.handshake(tls_acceptor.as_ref()).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!(
+                            "TLS handshake with client #{} ({}) failed: {}, closing connection",
+                            connection_id, socket_addr, e
+                        );
+                        return;
+                    }
+                };
+                Self::serve(
+                    &mut stream,
+                    connection_id,
+                    socket_addr,
+                    &server_broker,
+                    &shutdown,
+                )
+                .await;
+                // TLS clients are notified that the connection is closed on purpose
+                let _ = stream.shutdown().await;
+            });
+        }
 
-            let (mut stream, socket_addr) = self.listener.accept().await?;
+        info!(
+            "Shutting down kafka server on {}, disconnecting {} clients",
+            self.listeners(),
+            connections.len()
+        );
+        origin_server_broker.shutdown();
+        rejections.shutdown().await;
+        let shutdown_timeout = origin_server_broker.shutdown_timeout();
+        let drained = timeout(shutdown_timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "{} clients were not disconnected within {:?}, dropping them",
+                connections.len(),
+                shutdown_timeout
+            );
+            connections.shutdown().await;
+        }
+        http_endpoint.shutdown().await;
+        info!("Kafka server on {} stopped", self.listeners());
+        Ok(())
+    }
 
-            tokio::spawn(async move {
-                info!("Client {} connected to server", socket_addr);
+    /// Accepts the next client on any listener of the `Server`
+    async fn accept(&self) -> io::Result<(ClientStream, ClientAddr)> {
+        let tcp = async {
+            match &self.listener {
+                Some(listener) => {
+                    let (stream, socket_addr) = listener.accept().await?;
+                    Ok((ClientStream::Tcp(stream), ClientAddr::Tcp(socket_addr)))
+                }
+                None => pending().await,
+            }
+        };
+        #[cfg(unix)]
+        let unix = async {
+            match &self.unix_listener {
+                Some(unix_listener) => {
+                    let (stream, credentials) = unix_listener.accept().await?;
+                    Ok((ClientStream::Unix(stream), ClientAddr::Unix(credentials)))
+                }
+                None => pending().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let unix = pending();
+        tokio::select! {
+            accepted = tcp => accepted,
+            accepted = unix => accepted,
+        }
+    }
 
-                let mut buf_reader = BufReader::new(&mut stream);
+    /// Where the `Server` accepts clients, for logs
+    fn listeners(&self) -> String {
+        let mut listeners = vec![];
+        if let Some(Ok(socket_address)) = self.listener.as_ref().map(TcpListener::local_addr) {
+            listeners.push(format!("address {}", socket_address));
+        }
+        #[cfg(unix)]
+        if let Some(unix_listener) = &self.unix_listener {
+            listeners.push(format!("socket {}", unix_listener.path().display()));
+        }
+        listeners.join(" and ")
+    }
 
-                let client = match server_broker.new_client(socket_addr, &mut buf_reader).await {
-                    Ok(client) => client,
+    /// Serves the client connected with `connection_id` from `socket_addr` until it is disconnected
+    async fn serve(
+        stream: &mut ClientStream,
+        connection_id: u64,
+        socket_addr: ClientAddr,
+        server_broker: &ServerBroker,
+        shutdown: &ShutdownHandle,
+    ) {
+        let peer_principal = stream.peer_principal();
+        let mut buf_reader = BufReader::new(stream);
+
+        let new_client = tokio::select! {
+            new_client = server_broker.new_client(connection_id, socket_addr, peer_principal, &mut buf_reader) => new_client,
+            _ = shutdown.wait() => {
+                info!(
+                    "Client #{} ({}) disconnected before connection message because of shutdown",
+                    connection_id, socket_addr
+                );
+                return;
+            }
+        };
+        let client = match new_client {
+            Ok(client) => client,
+            Err(e) => {
+                server_broker.server_metrics().connection_error(&e);
+                if let Some(message) = e.to_service_message() {
+                    let _ =
+                        write_frame(buf_reader.get_mut(), &message.to_frame(Framing::Line)).await;
+                }
+                match e {
+                    ClientConnectionError::UnexpectedMessage(reason) => info!(
+                        r#"Failed to parse connection message from client #{} ({}): {}, closing connection"#,
+                        connection_id, socket_addr, reason
+                    ),
+                    ClientConnectionError::Aborted => info!(
+                        r#"Client #{} ({}) disconnected from server"#,
+                        connection_id, socket_addr
+                    ),
+                    ClientConnectionError::TimedOut => info!(
+                        r#"Client #{} ({}) did not send connection message within {:?}, closing connection"#,
+                        connection_id, socket_addr, CONNECTION_MESSAGE_TIMEOUT
+                    ),
+                    ClientConnectionError::ReaderError => info!(
+                        r#"Failed to read connection message from client #{} ({}), closing connection"#,
+                        connection_id, socket_addr
+                    ),
+                    ClientConnectionError::UnknownTopic(topic) => info!(
+                        r#"Client #{} ({}) tried to connect to unknown topic "{}", closing connection"#,
+                        connection_id, socket_addr, topic
+                    ),
+                    ClientConnectionError::InvalidTopicName(topic) => info!(
+                        r#"Client #{} ({}) tried to create topic with invalid name "{}", closing connection"#,
+                        connection_id, socket_addr, topic
+                    ),
+                    ClientConnectionError::DuplicateName(name) => info!(
+                        r#"Client #{} ({}) tried to connect with taken name "{}", closing connection"#,
+                        connection_id, socket_addr, name
+                    ),
+                    ClientConnectionError::AuthenticationFailed(identity) => warn!(
+                        r#"Client #{} ({}) failed to authenticate as {}, closing connection"#,
+                        connection_id, socket_addr, identity
+                    ),
+                    ClientConnectionError::AccessDenied(action) => warn!(
+                        r#"Client #{} ({}) denied access: {}, closing connection"#,
+                        connection_id, socket_addr, action
+                    ),
+                    ClientConnectionError::StorageError(e) => error!(
+                        r#"Failed to open topic log for client #{} ({}): {}, closing connection"#,
+                        connection_id, socket_addr, e
+                    ),
+                }
+                return;
+            }
+        };
+
+        match client {
+            Client::Subscriber(mut subscriber) => {
+                match &subscriber.group {
+                    Some(group) => info!(
+                        r#"For topics {:?} connected subscriber {} to group "{}" committed up to offsets {:?}"#,
+                        &subscriber.topics,
+                        &subscriber,
+                        group,
+                        subscriber.positions()
+                    ),
+                    None => info!(
+                        r#"For topics {:?} connected subscriber {} starting from offsets {:?}"#,
+                        &subscriber.topics,
+                        &subscriber,
+                        subscriber.positions()
+                    ),
+                }
+                match subscriber.listen(&mut buf_reader).await {
+                    Ok(_) => info!(
+                        r#"Subscriber {} disconnected from topics {:?}"#,
+                        &subscriber, &subscriber.topics
+                    ),
+                    Err(e) => error!(
+                        r#"Subscriber {} disconnected from topics {:?} with error: {:?}"#,
+                        &subscriber, &subscriber.topics, e
+                    ),
+                };
+            }
+            Client::Publisher(mut publisher) => {
+                info!(
+                    r#"For topics {:?} connected publisher {}"#,
+                    &publisher.topics, &publisher
+                );
+                match publisher.listen(&mut buf_reader).await {
+                    Ok(_) => info!(
+                        r#"Publisher {} disconnected from topics {:?}"#,
+                        &publisher, &publisher.topics
+                    ),
+                    Err(e) => error!(
+                        r#"Publisher {} disconnected from topics {:?} with error: {:?}"#,
+                        &publisher, &publisher.topics, e
+                    ),
+                };
+            }
+            Client::Admin(mut admin) => {
+                info!(r#"Connected admin {}"#, &admin);
+                match admin.listen(&mut buf_reader, server_broker).await {
+                    Ok(_) => info!(r#"Admin {} disconnected"#, &admin),
                     Err(e) => {
-                        match e {
-                            ClientConnectionError::UnexpectedMessage(message) => info!(
-                                r#"Failed to parse connection message from client {}: "{}", closing connection"#,
-                                socket_addr,
-                                String::from_utf8_lossy(message.as_slice())
-                            ),
-                            ClientConnectionError::Aborted => {
-                                info!(r#"Client {} disconnected from server"#, socket_addr)
-                            }
-                            ClientConnectionError::ReaderError => info!(
-                                r#"Failed to read connection message from client {}, closing connection"#,
-                                socket_addr
-                            ),
-                        }
-                        return;
+                        error!(r#"Admin {} disconnected with error: {:?}"#, &admin, e)
                     }
                 };
-
-                match client {
-                    Client::Subscriber(mut subscriber) => {
-                        info!(
-                            r#"For topic "{}" connected subscriber with ip {}"#,
-                            &subscriber.topic, &subscriber.socket_addr
-                        );
-                        match subscriber.listen(&mut stream).await {
-                            Ok(_) => info!(
-                                r#"Subscriber {} disconnected from topic "{}""#,
-                                &subscriber.socket_addr, &subscriber.topic
-                            ),
-                            Err(e) => error!(
-                                r#"Subscriber {} disconnected from topic "{}" with error: {:?}"#,
-                                &subscriber.socket_addr, &subscriber.topic, e
-                            ),
-                        };
-                    }
-                    Client::Publisher(mut publisher) => {
-                        info!(
-                            r#"For topic "{}" connected publisher with ip {}"#,
-                            &publisher.topic, &publisher.socket_addr
-                        );
-                        match publisher.listen(&mut buf_reader).await {
-                            Ok(_) => info!(
-                                r#"Publisher {} disconnected from topic "{}""#,
-                                &publisher.socket_addr, &publisher.topic
-                            ),
-                            Err(e) => error!(
-                                r#"Publisher {} disconnected from topic "{}" with error: {:?}"#,
-                                &publisher.socket_addr, &publisher.topic, e
-                            ),
-                        };
-                    }
-                }
-            });
+            }
         }
     }
 }
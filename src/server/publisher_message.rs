@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+/// Messages from `Publisher` clients connected with [MessageFormat::Json](crate::server::connection_message::MessageFormat::Json)
+///
+/// Each message is provided in JSON format:
+///
+/// ```JSON
+/// {"method": "send_message", "text": "<message_text>", "topic": "<topic_name>", "key": "<key>", "headers": {"<name>": "<value>"}}
+/// ```
+///
+/// - `topic` is optional, one of the topics the publisher is connected to. By default it is the first one
+/// - `key` and `headers` are optional and delivered to subscribers together with the message
+pub enum PublisherMessage {
+    /// Publishes `text` with the optional `key` and `headers` to the topic
+    SendMessage {
+        text: String,
+        #[serde(default)]
+        topic: Option<String>,
+        #[serde(default)]
+        key: Option<String>,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            serde_json::from_str::<PublisherMessage>(
+                r#"{"method": "send_message", "text": "Test message"}"#
+            )
+            .unwrap(),
+            PublisherMessage::SendMessage {
+                text: "Test message".to_string(),
+                topic: None,
+                key: None,
+                headers: HashMap::new(),
+            }
+        );
+
+        assert_eq!(
+            serde_json::from_str::<PublisherMessage>(
+                r#"{"method": "send_message", "text": "Test message", "topic": "Topic", "key": "user-42", "headers": {"trace_id": "a1b2"}}"#
+            )
+            .unwrap(),
+            PublisherMessage::SendMessage {
+                text: "Test message".to_string(),
+                topic: Some("Topic".to_string()),
+                key: Some("user-42".to_string()),
+                headers: HashMap::from([("trace_id".to_string(), "a1b2".to_string())]),
+            }
+        );
+
+        assert!(serde_json::from_str::<PublisherMessage>(
+            r#"{"method": "send_message", "text": "Test message", "headers": {"version": 2}}"#
+        )
+        .is_err());
+
+        assert!(serde_json::from_str::<PublisherMessage>(r#"{"method": "send_message"}"#).is_err());
+        assert!(serde_json::from_str::<PublisherMessage>(r#"Test message"#).is_err());
+    }
+}
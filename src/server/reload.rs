@@ -0,0 +1,25 @@
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+#[derive(Clone, Default)]
+/// Handle to reload the configuration of a running [Server](crate::server::Server), obtained from
+/// [Server::reload_handle](crate::server::Server::reload_handle)
+///
+/// The configuration is loaded by the loader set with
+/// [Server::with_config_loader](crate::server::Server::with_config_loader).
+/// Applied changes and changes that require a restart are logged
+pub struct ReloadHandle {
+    requested: Arc<Notify>,
+}
+
+impl ReloadHandle {
+    /// Requests reloading of the configuration. If the `Server` is not running yet, it is reloaded right after start
+    pub fn reload(&self) {
+        self.requested.notify_one();
+    }
+
+    /// Waits until reloading is requested
+    pub(crate) async fn requested(&self) {
+        self.requested.notified().await;
+    }
+}
@@ -0,0 +1,364 @@
+use crate::server::framing::Framing;
+use crate::server::topic::config::TopicConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+/// Service messages sent by the `Server` to clients
+///
+/// Each message is sent in the [Framing] negotiated by the client, with the payload in JSON format:
+///
+/// ```JSON
+/// {"type": "<message_type>", "data": {...}}
+/// ```
+pub enum ServiceMessage {
+    /// The message published to the topic, delivered to subscribers with
+    /// [MessageFormat::Json](crate::server::connection_message::MessageFormat::Json).
+    /// `time` is the Unix time (in seconds) when the message was published,
+    /// `publisher_id` is the connection id of the publisher, `key` and `headers` are set by the publisher
+    Message {
+        publisher_name: Option<String>,
+        publisher_id: u64,
+        from_topic: String,
+        message_text: String,
+        time: u64,
+        key: Option<String>,
+        headers: HashMap<String, String>,
+    },
+    /// The result of publishing a message, sent to publishers with
+    /// [AckMode::Message](crate::server::connection_message::AckMode::Message)
+    Ack(Ack),
+    /// The results of publishing all messages received at once, sent to publishers with
+    /// [AckMode::Batch](crate::server::connection_message::AckMode::Batch)
+    BatchAck { acks: Vec<Ack> },
+    /// The subscriber fell behind and `count` oldest messages of `topic` were skipped
+    MessagesSkipped { topic: String, count: u64 },
+    /// The client is disconnected by the `Server` for the `reason`
+    Disconnected { reason: DisconnectReason },
+    /// The request of the client failed. `message` is a human-readable description
+    Error { code: ErrorCode, message: String },
+    /// Sent to clients with presence events right after connecting:
+    /// the client itself and other clients connected to its topics
+    ConnectionInfo {
+        me: ClientInfo,
+        topics: BTreeMap<String, TopicClients>,
+        time: u64,
+    },
+    /// The publisher connected to `connected_topics` shared with the client
+    NewPublisher {
+        publisher: ClientSummary,
+        connected_topics: Vec<String>,
+        time: u64,
+    },
+    /// The subscriber connected to `connected_topics` shared with the client
+    NewSubscriber {
+        subscriber: ClientSummary,
+        connected_topics: Vec<String>,
+        time: u64,
+    },
+    /// The publisher disconnected from `disconnected_topics` shared with the client
+    PublisherLeft {
+        publisher: ClientSummary,
+        disconnected_topics: Vec<String>,
+        time: u64,
+    },
+    /// The subscriber disconnected from `disconnected_topics` shared with the client
+    SubscriberLeft {
+        subscriber: ClientSummary,
+        disconnected_topics: Vec<String>,
+        time: u64,
+    },
+    /// All topics of the `Server`, sent to admins in reply to
+    /// [AdminMessage::ListTopics](crate::server::admin_message::AdminMessage::ListTopics)
+    Topics { topics: Vec<TopicDescription> },
+    /// The topic described, or created by an admin
+    Topic(TopicDescription),
+    /// The topic is deleted by an admin
+    TopicDeleted { topic: String },
+    /// All clients connected to the `Server`, sent to admins in reply to
+    /// [AdminMessage::ListConnections](crate::server::admin_message::AdminMessage::ListConnections)
+    Connections {
+        connections: Vec<ConnectionDescription>,
+    },
+    /// The client is disconnected by an admin
+    ClientDisconnected { id: u64 },
+    /// The configuration of the `Server` is reloaded by an admin
+    ConfigReloaded(ConfigChanges),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+/// A client connected to the `Server`: its connection id and unique name, if any
+pub struct ClientSummary {
+    pub id: u64,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+/// The client receiving [ServiceMessage::ConnectionInfo]
+pub struct ClientInfo {
+    pub id: u64,
+    pub name: Option<String>,
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+/// The role of a client, `"publisher"`, `"subscriber"` or `"admin"` in serialized format
+pub enum Role {
+    Publisher,
+    Subscriber,
+    Admin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+/// A topic of the `Server`: its config, offsets of the oldest stored message and of the next message
+/// to be published, and clients connected to it
+pub struct TopicDescription {
+    pub name: String,
+    pub config: TopicConfig,
+    pub start_offset: u64,
+    pub next_offset: u64,
+    #[serde(flatten)]
+    pub clients: TopicClients,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone)]
+/// Changes of the reloaded configuration: `applied` to the running `Server`
+/// and ignored until the `Server` is restarted
+pub struct ConfigChanges {
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+/// A connection of the `Server`: the client, its address and topics
+pub struct ConnectionDescription {
+    pub id: u64,
+    pub name: Option<String>,
+    pub role: Role,
+    pub address: String,
+    pub topics: Vec<String>,
+    pub principal: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone)]
+/// Clients connected to a topic
+pub struct TopicClients {
+    pub publishers: Vec<ClientSummary>,
+    pub subscribers: Vec<ClientSummary>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+/// Why the request of the client failed
+pub enum ErrorCode {
+    /// The connection message is not in the expected format
+    UnexpectedMessage,
+    /// There is no such topic and topics cannot be created by clients
+    UnknownTopic,
+    /// The topic name does not match the topic name pattern of the `Server`
+    InvalidTopicName,
+    /// Another connected client already has the requested name
+    DuplicateName,
+    /// The topic created by an admin already exists
+    TopicExists,
+    /// The topic config is not valid
+    InvalidConfig,
+    /// There is no connected client with the id
+    UnknownClient,
+    /// The `Server` has as many connected clients as its limit allows
+    TooManyConnections,
+    /// The configuration cannot be reloaded or is not valid
+    ReloadFailed,
+    /// The credentials of the client are missing or not accepted
+    AuthenticationFailed,
+    /// The client is not allowed to access the topic or manage the `Server`
+    AccessDenied,
+    /// The topic log cannot be opened
+    StorageError,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+/// Why the `Server` disconnects the client
+pub enum DisconnectReason {
+    /// The subscriber fell too far behind the published messages
+    SlowConsumer,
+    /// An admin disconnected the client
+    Admin,
+    /// An admin deleted a topic of the client
+    TopicDeleted,
+    /// The `Server` is shutting down
+    Shutdown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+/// The result of publishing a message
+///
+/// In serialized format it is `{"sequence": <sequence>, "offset": <offset>}`
+/// or `{"sequence": <sequence>, "error": "<publish_error>"}`
+pub struct Ack {
+    /// Number of the message received from the publisher connection, starting from 0
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub result: AckResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AckResult {
+    /// The message is appended to the topic log with the offset
+    Offset(u64),
+    /// The message is not published
+    Error(PublishError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+/// Why the message is not published
+pub enum PublishError {
+    /// The message is bigger than `max_message_size` of the topic
+    MessageTooLarge,
+    /// The message is not a valid [PublisherMessage](crate::server::publisher_message::PublisherMessage)
+    InvalidMessage,
+    /// The publisher is not connected to the topic chosen in the message
+    UnknownTopic,
+    /// The message cannot be appended to the topic log. The publisher is disconnected
+    StorageError,
+}
+
+impl ServiceMessage {
+    /// Serialized message encoded in `framing`
+    pub fn to_frame(&self, framing: Framing) -> Vec<u8> {
+        framing.encode(&serde_json::to_vec(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error() {
+        assert_eq!(
+            ServiceMessage::Error {
+                code: ErrorCode::InvalidTopicName,
+                message: "not allowed".to_string(),
+            }
+            .to_frame(Framing::Line),
+            b"{\"type\":\"error\",\"data\":{\"code\":\"invalid_topic_name\",\"message\":\"not allowed\"}}\n"
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_ack() {
+        assert_eq!(
+            ServiceMessage::Ack(Ack {
+                sequence: 0,
+                result: AckResult::Offset(42),
+            })
+            .to_frame(Framing::Line),
+            b"{\"type\":\"ack\",\"data\":{\"sequence\":0,\"offset\":42}}\n".to_vec()
+        );
+
+        assert_eq!(
+            serde_json::to_value(ServiceMessage::BatchAck {
+                acks: vec![
+                    Ack {
+                        sequence: 1,
+                        result: AckResult::Offset(43),
+                    },
+                    Ack {
+                        sequence: 2,
+                        result: AckResult::Error(PublishError::MessageTooLarge),
+                    },
+                ],
+            })
+            .unwrap(),
+            serde_json::json!({
+                "type": "batch_ack",
+                "data": {
+                    "acks": [
+                        {"sequence": 1, "offset": 43},
+                        {"sequence": 2, "error": "message_too_large"}
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            ServiceMessage::MessagesSkipped {
+                topic: "Topic".to_string(),
+                count: 3
+            }
+            .to_frame(Framing::Line),
+            b"{\"type\":\"messages_skipped\",\"data\":{\"topic\":\"Topic\",\"count\":3}}\n"
+                .to_vec()
+        );
+
+        assert_eq!(
+            ServiceMessage::Disconnected {
+                reason: DisconnectReason::SlowConsumer
+            }
+            .to_frame(Framing::Line),
+            b"{\"type\":\"disconnected\",\"data\":{\"reason\":\"slow_consumer\"}}\n".to_vec()
+        );
+
+        assert_eq!(
+            serde_json::to_value(ServiceMessage::Message {
+                publisher_name: Some("sensor".to_string()),
+                publisher_id: 5,
+                from_topic: "test_topic".to_string(),
+                message_text: "Test message".to_string(),
+                time: 1688720400,
+                key: Some("user-42".to_string()),
+                headers: HashMap::from([("trace_id".to_string(), "a1b2".to_string())]),
+            })
+            .unwrap(),
+            serde_json::json!({
+                "type": "message",
+                "data": {
+                    "publisher_name": "sensor",
+                    "publisher_id": 5,
+                    "from_topic": "test_topic",
+                    "message_text": "Test message",
+                    "time": 1688720400,
+                    "key": "user-42",
+                    "headers": {"trace_id": "a1b2"}
+                }
+            })
+        );
+
+        assert_eq!(
+            serde_json::to_value(ServiceMessage::ConnectionInfo {
+                me: ClientInfo {
+                    id: 7,
+                    name: Some("Admin publisher".to_string()),
+                    role: Role::Publisher,
+                },
+                topics: BTreeMap::from([(
+                    "topic1".to_string(),
+                    TopicClients {
+                        publishers: vec![],
+                        subscribers: vec![ClientSummary { id: 3, name: None }],
+                    },
+                )]),
+                time: 1688720400,
+            })
+            .unwrap(),
+            serde_json::json!({
+                "type": "connection_info",
+                "data": {
+                    "me": {"id": 7, "name": "Admin publisher", "role": "publisher"},
+                    "topics": {"topic1": {"publishers": [], "subscribers": [{"id": 3, "name": null}]}},
+                    "time": 1688720400
+                }
+            })
+        );
+    }
+}
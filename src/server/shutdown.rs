@@ -0,0 +1,42 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Clone)]
+/// Handle to stop a running [Server](crate::server::Server), obtained from
+/// [Server::shutdown_handle](crate::server::Server::shutdown_handle)
+///
+/// After [ShutdownHandle::shutdown] the `Server` stops accepting connections, sends messages already published
+/// to subscribers, notifies all clients with
+/// [ServiceMessage::Disconnected](crate::server::service_message::ServiceMessage::Disconnected)
+/// and returns from [Server::run](crate::server::Server::run)
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Starts the shutdown of the `Server`. If the `Server` is not running yet, it will stop right after start
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Returns `true` if the shutdown has been started
+    pub fn is_shutdown(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Waits until the shutdown is started
+    pub(crate) async fn wait(&self) {
+        let mut receiver = self.sender.subscribe();
+        // the sender is owned by `self`, so the channel cannot be closed
+        let _ = receiver.wait_for(|shutdown| *shutdown).await;
+    }
+}
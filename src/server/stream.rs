@@ -0,0 +1,204 @@
+use crate::server::tls::certificate_principal;
+use std::io::{ErrorKind, IoSlice};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::time::timeout;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// How long the `Server` waits for the TLS handshake of a client
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection of a client accepted by the `Server`: plain TCP, encrypted with TLS or a Unix domain socket.
+/// Clients are served identically over all of them, as over any other [AsyncRead] + [AsyncWrite] stream
+pub enum ClientStream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// Where a client is connected from
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ClientAddr {
+    /// IP address and port of a TCP client
+    Tcp(SocketAddr),
+    /// Credentials of the process connected over a Unix domain socket
+    Unix(PeerCredentials),
+}
+
+/// Credentials of the process on the other end of a Unix domain socket, as reported by the operating system
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    /// Not reported on some platforms
+    pub pid: Option<i32>,
+}
+
+impl ClientStream {
+    /// Completes accepting the stream: with `tls_acceptor` the TLS handshake of a TCP client is performed.
+    /// Unix domain socket clients are not encrypted
+    ///
+    /// # Errors
+    /// - If the handshake does not finish within [TLS_HANDSHAKE_TIMEOUT], function will return [ErrorKind::TimedOut]
+    /// - All other errors are inherited from [TlsAcceptor::accept]
+    pub(crate) async fn handshake(self, tls_acceptor: Option<&TlsAcceptor>) -> io::Result<Self> {
+        let (stream, tls_acceptor) = match (self, tls_acceptor) {
+            (ClientStream::Tcp(stream), Some(tls_acceptor)) => (stream, tls_acceptor),
+            (stream, _) => return Ok(stream),
+        };
+        match timeout(TLS_HANDSHAKE_TIMEOUT, tls_acceptor.accept(stream)).await {
+            Ok(stream) => Ok(ClientStream::Tls(Box::new(stream?))),
+            Err(_) => Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "TLS handshake timed out",
+            )),
+        }
+    }
+
+    /// Principal established by the connection itself, if any: the subject of the client certificate
+    /// verified during the TLS handshake or the uid of the process connected over a Unix domain socket
+    pub fn peer_principal(&self) -> Option<String> {
+        match self {
+            ClientStream::Tcp(_) => None,
+            ClientStream::Tls(stream) => {
+                let (_, connection) = stream.get_ref();
+                certificate_principal(connection.peer_certificates()?.first()?)
+            }
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => Some(PeerCredentials::of(stream).ok()?.principal()),
+        }
+    }
+}
+
+impl PeerCredentials {
+    /// Credentials of the process connected to `stream`
+    ///
+    /// # Errors
+    /// All errors are inherited from [UnixStream::peer_cred]
+    #[cfg(unix)]
+    pub(crate) fn of(stream: &UnixStream) -> io::Result<Self> {
+        let credentials = stream.peer_cred()?;
+        Ok(Self {
+            uid: credentials.uid(),
+            gid: credentials.gid(),
+            pid: credentials.pid(),
+        })
+    }
+
+    /// Principal of the process for clients without credentials of their own: `uid:<uid>`
+    pub fn principal(&self) -> String {
+        format!("uid:{}", self.uid)
+    }
+}
+
+impl From<SocketAddr> for ClientAddr {
+    fn from(socket_addr: SocketAddr) -> Self {
+        ClientAddr::Tcp(socket_addr)
+    }
+}
+
+impl fmt::Display for ClientAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientAddr::Tcp(socket_addr) => write!(f, "{}", socket_addr),
+            ClientAddr::Unix(credentials) => {
+                write!(f, "unix:uid={},gid={}", credentials.uid, credentials.gid)?;
+                match credentials.pid {
+                    Some(pid) => write!(f, ",pid={}", pid),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            ClientStream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            ClientStream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            ClientStream::Tls(stream) => Pin::new(stream.as_mut()).poll_write_vectored(cx, bufs),
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            ClientStream::Tcp(stream) => stream.is_write_vectored(),
+            ClientStream::Tls(stream) => stream.is_write_vectored(),
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            ClientStream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            ClientStream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+            #[cfg(unix)]
+            ClientStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Writes `frame` to `stream` and flushes it, so that no part of the frame is held back by TLS
+///
+/// # Errors
+/// All errors are inherited from [AsyncWriteExt::write_all] and [AsyncWriteExt::flush]
+pub(crate) async fn write_frame<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    frame: &[u8],
+) -> io::Result<()> {
+    stream.write_all(frame).await?;
+    stream.flush().await
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(tag = "method", rename_all = "lowercase")]
+/// Messages from `Subscriber` clients sent after the [ConnectionMessage](crate::server::connection_message::ConnectionMessage)
+///
+/// Each line is a message from a `Subscriber`. Messages are provided in JSON format:
+///
+/// ```JSON
+/// {"method": "commit", "topic": "<topic_name>", "offset": <offset>}
+/// ```
+pub enum SubscriberMessage {
+    /// Commits messages of the consumer group delivered to the `Subscriber`:
+    /// all of them or only ones with offsets up to `offset` (inclusive).
+    /// Without `topic` messages of all topics of the `Subscriber` are committed.
+    /// Committed messages are not delivered to the group again
+    Commit {
+        #[serde(default)]
+        topic: Option<String>,
+        #[serde(default)]
+        offset: Option<u64>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            serde_json::from_str::<SubscriberMessage>(r#"{"method": "commit"}"#).unwrap(),
+            SubscriberMessage::Commit {
+                topic: None,
+                offset: None
+            }
+        );
+
+        assert_eq!(
+            serde_json::from_str::<SubscriberMessage>(
+                r#"{"method": "commit", "topic": "Topic", "offset": 7}"#
+            )
+            .unwrap(),
+            SubscriberMessage::Commit {
+                topic: Some("Topic".to_string()),
+                offset: Some(7)
+            }
+        );
+
+        assert!(serde_json::from_str::<SubscriberMessage>(r#"{"method": "publish"}"#).is_err());
+    }
+}
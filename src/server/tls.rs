@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::RootCertStore;
+use tokio_rustls::TlsAcceptor;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+/// TLS termination of client connections
+///
+/// ```TOML
+/// [tls]
+/// cert_file = "./server.pem"
+/// key_file = "./server.key"
+/// client_ca_file = "./ca.pem"
+/// require_client_cert = true
+/// ```
+///
+/// - `cert_file` - PEM file with the certificate chain of the `Server`, starting with its own certificate
+/// - `key_file` - PEM file with the private key of the `Server` (PKCS#8, PKCS#1 or SEC1)
+/// - `client_ca_file` - PEM file with certificates of authorities issuing client certificates.
+///   Without it clients are not asked for certificates
+/// - `require_client_cert` - clients without a certificate are rejected (mutual TLS), `false` by default
+///
+/// The principal of a client with a verified certificate is the common name of the certificate subject
+/// (the whole subject, if it has no common name), see [AuthConfig](crate::server::auth::AuthConfig)
+pub struct TlsConfig {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+    #[serde(default)]
+    pub client_ca_file: Option<PathBuf>,
+    #[serde(default)]
+    pub require_client_cert: bool,
+}
+
+impl TlsConfig {
+    /// Checks that all values can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.require_client_cert && self.client_ca_file.is_none() {
+            return Err("tls: require_client_cert needs client_ca_file".to_string());
+        }
+        Ok(())
+    }
+
+    /// Loads certificates and keys of the config
+    ///
+    /// # Errors
+    /// - All errors are inherited from [File::open]
+    /// - If a file is not valid or the key does not match the certificate,
+    ///   function will return [ErrorKind::InvalidData]
+    pub fn acceptor(&self) -> io::Result<TlsAcceptor> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?;
+        let builder = match &self.client_ca_file {
+            Some(client_ca_file) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in load_certificates(client_ca_file)? {
+                    roots
+                        .add(certificate)
+                        .map_err(|e| in_file("client CA file", client_ca_file, invalid_data(e)))?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = match self.require_client_cert {
+                    true => verifier,
+                    false => verifier.allow_unauthenticated(),
+                };
+                builder.with_client_cert_verifier(verifier.build().map_err(invalid_data)?)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder
+            .with_single_cert(
+                load_certificates(&self.cert_file)?,
+                load_private_key(&self.key_file)?,
+            )
+            .map_err(|e| in_file("key file", &self.key_file, invalid_data(e)))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// Returns the principal of the client with `certificate`: the common name of the subject,
+/// or the whole subject if it has no common name
+pub(crate) fn certificate_principal(certificate: &CertificateDer) -> Option<String> {
+    let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+    let subject = certificate.subject();
+    let principal = match subject.iter_common_name().next() {
+        Some(common_name) => common_name.as_str().ok()?.to_string(),
+        None => subject.to_string(),
+    };
+    (!principal.is_empty()).then_some(principal)
+}
+
+/// Loads all certificates of PEM file `path`
+fn load_certificates(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).map_err(|e| in_file("certificate file", path, e))?;
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(|e| in_file("certificate file", path, e))?;
+    match certificates.is_empty() {
+        true => Err(in_file(
+            "certificate file",
+            path,
+            io::Error::new(ErrorKind::InvalidData, "no certificates"),
+        )),
+        false => Ok(certificates),
+    }
+}
+
+/// Loads the first private key of PEM file `path`
+fn load_private_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).map_err(|e| in_file("key file", path, e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| in_file("key file", path, e))?
+        .ok_or_else(|| {
+            in_file(
+                "key file",
+                path,
+                io::Error::new(ErrorKind::InvalidData, "no private key"),
+            )
+        })
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e)
+}
+
+/// Adds the file to the description of `e`
+fn in_file(kind: &str, path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{} {} -- {}", kind, path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{CertificateParams, DnType, KeyPair};
+
+    fn certificate(common_name: Option<&str>) -> CertificateDer<'static> {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "simple-kafka");
+        if let Some(common_name) = common_name {
+            params
+                .distinguished_name
+                .push(DnType::CommonName, common_name);
+        }
+        let key_pair = KeyPair::generate().unwrap();
+        params.self_signed(&key_pair).unwrap().der().clone()
+    }
+
+    #[test]
+    fn test_certificate_principal() {
+        assert_eq!(
+            certificate_principal(&certificate(Some("billing"))),
+            Some("billing".to_string())
+        );
+        assert_eq!(
+            certificate_principal(&certificate(None)),
+            Some("O=simple-kafka".to_string())
+        );
+        assert_eq!(
+            certificate_principal(&CertificateDer::from(vec![1, 2, 3])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_acceptor() {
+        let dir = std::env::temp_dir().join(format!("simple-kafka-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_pair = KeyPair::generate().unwrap();
+        let certificate = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        std::fs::write(dir.join("server.pem"), certificate.pem()).unwrap();
+        std::fs::write(dir.join("server.key"), key_pair.serialize_pem()).unwrap();
+
+        let config = TlsConfig {
+            cert_file: dir.join("server.pem"),
+            key_file: dir.join("server.key"),
+            client_ca_file: Some(dir.join("server.pem")),
+            require_client_cert: true,
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.acceptor().is_ok());
+        assert!(TlsConfig {
+            client_ca_file: None,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        // the certificate is not a key
+        assert!(TlsConfig {
+            key_file: dir.join("server.pem"),
+            ..config.clone()
+        }
+        .acceptor()
+        .is_err());
+        assert!(TlsConfig {
+            cert_file: dir.join("missing.pem"),
+            ..config
+        }
+        .acceptor()
+        .is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const DEFAULT_CHANNEL_CAPACITY: usize = 32usize;
+/// Channel slots are allocated when the topic is opened, so the capacity is kept small
+pub const MAX_CHANNEL_CAPACITY: usize = 65536;
+const DEFAULT_SEGMENT_SIZE: u64 = 16 * 1024 * 1024; // in bytes
+const DEFAULT_TOPIC_NAME_PATTERN: &str = r"^[a-zA-Z0-9_./-]{3,64}$";
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+/// What happens when a `Subscriber` falls too far behind the messages published to the topic
+///
+/// In serialized format it is `"drop_oldest"`, `"block"` or `"disconnect"`
+pub enum SlowConsumerPolicy {
+    /// The oldest messages are skipped and the subscriber receives
+    /// a [ServiceMessage::MessagesSkipped](crate::server::service_message::ServiceMessage::MessagesSkipped)
+    DropOldest,
+    /// Publishers wait until the slowest subscriber catches up
+    Block,
+    /// The subscriber receives a
+    /// [ServiceMessage::Disconnected](crate::server::service_message::ServiceMessage::Disconnected) and is disconnected
+    #[default]
+    Disconnect,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// How long messages are kept in the topic log
+///
+/// The log is cleaned up by whole segments, so the oldest segment is removed only when all of its messages expire.
+/// The last segment is never removed. Limits are checked when the topic is opened and when a new segment is started.
+/// Without limits all messages are kept
+pub struct Retention {
+    /// Maximal total size of the topic log in bytes
+    pub max_bytes: Option<u64>,
+    /// Maximal age of messages in seconds
+    pub max_age: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Settings of a topic
+///
+/// Settings are set when the topic is created and stored together with the topic log.
+/// In serialized format all fields are optional:
+///
+/// ```JSON
+/// {
+///     "channel_capacity": 32,
+///     "max_message_size": 1024,
+///     "segment_size": 16777216,
+///     "retention": {"max_bytes": 1073741824, "max_age": 86400},
+///     "slow_consumer_policy": "<slow_consumer_policy>"
+/// }
+/// ```
+///
+/// - Where `<slow_consumer_policy>` is the serialized [SlowConsumerPolicy](SlowConsumerPolicy)
+pub struct TopicConfig {
+    /// How many messages a subscriber may fall behind before [SlowConsumerPolicy] is applied,
+    /// at most [MAX_CHANNEL_CAPACITY]
+    pub channel_capacity: usize,
+    /// Maximal size of a message payload in bytes (without the delimiter or length prefix). Bigger messages are rejected
+    pub max_message_size: Option<usize>,
+    /// Size of the topic log segment in bytes, after which a new segment is started
+    pub segment_size: u64,
+    pub retention: Retention,
+    pub slow_consumer_policy: SlowConsumerPolicy,
+}
+
+impl Default for TopicConfig {
+    fn default() -> Self {
+        Self {
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            max_message_size: None,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            retention: Retention::default(),
+            slow_consumer_policy: SlowConsumerPolicy::default(),
+        }
+    }
+}
+
+impl TopicConfig {
+    /// Checks that the config values can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.channel_capacity == 0 || self.channel_capacity > MAX_CHANNEL_CAPACITY {
+            return Err(format!(
+                "channel_capacity must be positive and not greater than {}",
+                MAX_CHANNEL_CAPACITY
+            ));
+        }
+        if self.segment_size == 0 {
+            return Err("segment_size must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Loads config stored in `path`. Returns `None` if there is no such file
+    ///
+    /// # Errors
+    /// - All errors are inherited from [fs::read_to_string]
+    /// - If the file is not a valid config, function will return [io::ErrorKind::InvalidData]
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let config = fs::read_to_string(path)?;
+        serde_json::from_str(&config)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Stores config in `path`
+    ///
+    /// # Errors
+    /// All errors are inherited from [fs::write]
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(default)]
+/// Topic settings of the `Server`
+///
+/// In serialized format:
+///
+/// ```JSON
+/// {
+///     "default": <topic_config>,
+///     "topics": {"<topic_name>": <topic_config>},
+///     "topic_name_pattern": "^[a-zA-Z0-9_./-]{3,64}$",
+///     "auto_create": true
+/// }
+/// ```
+///
+/// - `default` is the [TopicConfig](TopicConfig) of topics created by clients without their own config
+/// - `topics` are topics declared in advance. They are created when the `Server` starts,
+///   and their configs replace the stored ones
+/// - `topic_name_pattern` is a regular expression that names of new topics must match.
+///   By default names are 3 to 64 letters, digits, `_`, `-` and level separators `/` and `.`.
+///   With `null` any name is allowed
+pub struct TopicsConfig {
+    pub default: TopicConfig,
+    pub topics: HashMap<String, TopicConfig>,
+    pub topic_name_pattern: Option<String>,
+    /// Whether a topic can be created by a client connecting to it
+    pub auto_create: bool,
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        Self {
+            default: TopicConfig::default(),
+            topics: HashMap::default(),
+            topic_name_pattern: Some(DEFAULT_TOPIC_NAME_PATTERN.to_string()),
+            auto_create: true,
+        }
+    }
+}
+
+impl TopicsConfig {
+    /// Loads topic settings from JSON file `path`
+    ///
+    /// # Errors
+    /// - All errors are inherited from [fs::read_to_string]
+    /// - If the file is not valid, function will return [io::ErrorKind::InvalidData]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let config = fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&config)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        config
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(config)
+    }
+
+    /// Checks that all topic configs can be used and names of declared topics match `topic_name_pattern`
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        self.default
+            .validate()
+            .map_err(|e| format!("default: {}", e))?;
+        let topic_name_regex = self.topic_name_regex()?;
+        for (topic, config) in &self.topics {
+            config
+                .validate()
+                .map_err(|e| format!(r#"topic "{}": {}"#, topic, e))?;
+            if topic_name_regex
+                .as_ref()
+                .is_some_and(|regex| !regex.is_match(topic))
+            {
+                return Err(format!(
+                    r#"topic "{}": name does not match topic_name_pattern"#,
+                    topic
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiled `topic_name_pattern`
+    ///
+    /// # Errors
+    /// Returns description of the error if the pattern is not a valid regular expression
+    pub fn topic_name_regex(&self) -> Result<Option<Regex>, String> {
+        self.topic_name_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| format!("topic_name_pattern: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            serde_json::from_str::<TopicConfig>(r#"{}"#).unwrap(),
+            TopicConfig::default()
+        );
+
+        assert_eq!(
+            serde_json::from_str::<TopicConfig>(
+                r#"{"slow_consumer_policy": "drop_oldest", "channel_capacity": 1024, "retention": {"max_age": 60}}"#
+            )
+            .unwrap(),
+            TopicConfig {
+                channel_capacity: 1024,
+                retention: Retention {
+                    max_bytes: None,
+                    max_age: Some(60)
+                },
+                slow_consumer_policy: SlowConsumerPolicy::DropOldest,
+                ..TopicConfig::default()
+            }
+        );
+
+        assert!(
+            serde_json::from_str::<TopicConfig>(r#"{"slow_consumer_policy": "wait"}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(TopicConfig::default().validate().is_ok());
+        assert!(TopicConfig {
+            channel_capacity: 0,
+            ..TopicConfig::default()
+        }
+        .validate()
+        .is_err());
+        assert!(TopicConfig {
+            channel_capacity: MAX_CHANNEL_CAPACITY + 1,
+            ..TopicConfig::default()
+        }
+        .validate()
+        .is_err());
+
+        let config = serde_json::from_str::<TopicsConfig>(
+            r#"{"auto_create": false, "topics": {"telemetry": {"segment_size": 0}}}"#,
+        )
+        .unwrap();
+        assert!(!config.auto_create);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_topic_name_pattern() {
+        let regex = TopicsConfig::default().topic_name_regex().unwrap().unwrap();
+        assert!(regex.is_match("test_topic"));
+        assert!(regex.is_match("sensors/kitchen/temperature"));
+        assert!(regex.is_match("Topic-42"));
+        assert!(!regex.is_match(""));
+        assert!(!regex.is_match("ab"));
+        assert!(!regex.is_match(&"a".repeat(65)));
+        assert!(!regex.is_match("topic name"));
+
+        let config = serde_json::from_str::<TopicsConfig>(r#"{"topics": {"x": {}}}"#).unwrap();
+        assert!(config.validate().is_err());
+
+        let config = serde_json::from_str::<TopicsConfig>(
+            r#"{"topics": {"x": {}}, "topic_name_pattern": null}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let config =
+            serde_json::from_str::<TopicsConfig>(r#"{"topic_name_pattern": "["}"#).unwrap();
+        assert!(config.validate().is_err());
+    }
+}
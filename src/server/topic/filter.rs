@@ -0,0 +1,93 @@
+const LEVEL_SEPARATORS: [char; 2] = ['/', '.'];
+const SINGLE_LEVEL_WILDCARD: &str = "+";
+const MULTI_LEVEL_WILDCARD: &str = "#";
+
+/// Topic name with wildcards matching a set of topics
+///
+/// Topic names are hierarchical: levels are separated by `/` or `.` (e.g. `sensors/kitchen/temperature`).
+/// Wildcards are MQTT-style and take a whole level:
+/// - `+` matches exactly one level (`sensors/+/temperature` matches `sensors/kitchen/temperature`)
+/// - `#` matches any number of remaining levels, including none, and must be the last level
+///   (`sensors/#` matches `sensors`, `sensors/kitchen` and `sensors/kitchen/temperature`)
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub(crate) struct TopicFilter {
+    pub pattern: String,
+    levels: Vec<String>,
+}
+
+impl TopicFilter {
+    /// Whether `name` contains wildcards and should be parsed as a [TopicFilter]
+    pub fn is_filter(name: &str) -> bool {
+        name.contains(SINGLE_LEVEL_WILDCARD) || name.contains(MULTI_LEVEL_WILDCARD)
+    }
+
+    /// Parses `pattern`. Returns `None` if wildcards are not whole levels or `#` is not the last level
+    pub fn parse(pattern: &str) -> Option<Self> {
+        let levels: Vec<String> = pattern.split(LEVEL_SEPARATORS).map(String::from).collect();
+        for (i, level) in levels.iter().enumerate() {
+            let is_wildcard = level == SINGLE_LEVEL_WILDCARD || level == MULTI_LEVEL_WILDCARD;
+            if !is_wildcard && Self::is_filter(level) {
+                return None;
+            }
+            if level == MULTI_LEVEL_WILDCARD && i != levels.len() - 1 {
+                return None;
+            }
+        }
+        Some(Self {
+            pattern: pattern.to_string(),
+            levels,
+        })
+    }
+
+    /// Whether topic `name` matches the filter
+    pub fn matches(&self, name: &str) -> bool {
+        let mut levels = name.split(LEVEL_SEPARATORS);
+        for filter_level in &self.levels {
+            if filter_level == MULTI_LEVEL_WILDCARD {
+                return true;
+            }
+            match levels.next() {
+                Some(level) if filter_level == SINGLE_LEVEL_WILDCARD || filter_level == level => {}
+                _ => return false,
+            }
+        }
+        levels.next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert!(TopicFilter::is_filter("sensors/+/temperature"));
+        assert!(!TopicFilter::is_filter("sensors/kitchen/temperature"));
+
+        assert!(TopicFilter::parse("sensors/+/temperature").is_some());
+        assert!(TopicFilter::parse("sensors.#").is_some());
+        assert!(TopicFilter::parse("#").is_some());
+        assert!(TopicFilter::parse("sensors/#/temperature").is_none());
+        assert!(TopicFilter::parse("sensors/kitchen+/temperature").is_none());
+        assert!(TopicFilter::parse("sensors/kitchen#").is_none());
+    }
+
+    #[test]
+    fn test_matches() {
+        let filter = TopicFilter::parse("sensors/+/temperature").unwrap();
+        assert!(filter.matches("sensors/kitchen/temperature"));
+        assert!(filter.matches("sensors.hall.temperature"));
+        assert!(!filter.matches("sensors/kitchen/humidity"));
+        assert!(!filter.matches("sensors/temperature"));
+        assert!(!filter.matches("sensors/kitchen/temperature/max"));
+
+        let filter = TopicFilter::parse("sensors/#").unwrap();
+        assert!(filter.matches("sensors"));
+        assert!(filter.matches("sensors/kitchen"));
+        assert!(filter.matches("sensors/kitchen/temperature"));
+        assert!(!filter.matches("devices/kitchen"));
+
+        let filter = TopicFilter::parse("#").unwrap();
+        assert!(filter.matches("any/topic"));
+    }
+}
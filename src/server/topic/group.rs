@@ -0,0 +1,289 @@
+use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::watch;
+use tokio::task::spawn_blocking;
+
+/// Consumer group of a `Topic`
+///
+/// Messages of the topic are distributed among the members of the group: each message is handed out to
+/// only one member. A member commits messages after processing them, and the group keeps `committed` offset:
+/// all messages before it are processed. The committed offset is stored in the group file, so a restarted
+/// group resumes where it left off
+///
+/// Groups are pull-based: the topic is not split into partitions assigned to members, instead each member
+/// claims the next message when it is ready for it. When a member leaves, all messages handed out to it
+/// but not committed are returned to the group and claimed by the remaining members first (rebalance).
+/// Members waiting for messages are woken up by every rebalance
+pub(crate) struct ConsumerGroup {
+    pub name: String,
+    path: Option<PathBuf>,
+    state: Mutex<GroupState>,
+    rebalances: watch::Sender<u64>,
+    stored: tokio::sync::Mutex<u64>, // committed offset in the group file, held while the file is written
+}
+
+struct GroupState {
+    committed: u64,
+    next_offset: u64, // offset of the next message that has never been handed out
+    in_flight: BTreeMap<u64, InFlight>, // handed out but not committed messages
+    returned: BTreeSet<u64>, // messages returned to the group by left members
+    processed: BTreeSet<u64>, // committed messages after `committed` offset
+    members: BTreeSet<u64>,
+    next_member_id: u64,
+    generation: u64,
+}
+
+struct InFlight {
+    member_id: u64,
+    delivered: bool,
+}
+
+impl ConsumerGroup {
+    /// Loads group `name` committed offset from `path`.
+    /// If there is no group file, the group starts from `start_offset`
+    ///
+    /// # Errors
+    /// All errors are inherited from [fs::read_to_string]
+    pub fn open(name: String, path: Option<PathBuf>, start_offset: u64) -> io::Result<Self> {
+        let committed = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)?
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            _ => start_offset,
+        };
+        let (rebalances, _) = watch::channel(0);
+        Ok(Self {
+            name,
+            path,
+            state: Mutex::new(GroupState {
+                committed,
+                next_offset: committed,
+                in_flight: BTreeMap::new(),
+                returned: BTreeSet::new(),
+                processed: BTreeSet::new(),
+                members: BTreeSet::new(),
+                next_member_id: 0,
+                generation: 0,
+            }),
+            rebalances,
+            stored: tokio::sync::Mutex::new(committed),
+        })
+    }
+
+    /// Offset before which all messages of the group are processed
+    pub fn committed(&self) -> u64 {
+        self.state.lock().unwrap().committed
+    }
+
+    /// Adds a new member to the group and returns its id.
+    /// The receiver is notified every time the group rebalances
+    pub fn join(&self, topic: &str) -> (u64, watch::Receiver<u64>) {
+        let mut state = self.state.lock().unwrap();
+        let member_id = state.next_member_id;
+        state.next_member_id += 1;
+        state.members.insert(member_id);
+        self.rebalance(topic, &mut state);
+        (member_id, self.rebalances.subscribe())
+    }
+
+    /// Removes the member from the group and returns its uncommitted messages to the group
+    pub fn leave(&self, topic: &str, member_id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.members.remove(&member_id);
+        let returned: Vec<u64> = state
+            .in_flight
+            .iter()
+            .filter(|(_, in_flight)| in_flight.member_id == member_id)
+            .map(|(offset, _)| *offset)
+            .collect();
+        for offset in returned {
+            state.in_flight.remove(&offset);
+            state.returned.insert(offset);
+        }
+        self.rebalance(topic, &mut state);
+    }
+
+    fn rebalance(&self, topic: &str, state: &mut GroupState) {
+        state.generation += 1;
+        info!(
+            r#"Group "{}" of topic "{}" rebalanced (generation {}): members {:?}, {} messages returned"#,
+            self.name,
+            topic,
+            state.generation,
+            state.members,
+            state.returned.len()
+        );
+        self.rebalances.send_replace(state.generation);
+    }
+
+    /// Hands out the next message to the member. `log_next_offset` is the offset of the next message
+    /// to be published to the topic. Returns `None` if there are no messages to hand out
+    pub fn claim(&self, member_id: u64, log_next_offset: u64) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        let offset = match state.returned.pop_first() {
+            Some(offset) => offset,
+            None if state.next_offset < log_next_offset => {
+                state.next_offset += 1;
+                state.next_offset - 1
+            }
+            None => return None,
+        };
+        state.in_flight.insert(
+            offset,
+            InFlight {
+                member_id,
+                delivered: false,
+            },
+        );
+        Some(offset)
+    }
+
+    /// Marks message handed out to the member as delivered to the client
+    pub fn delivered(&self, member_id: u64, offset: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(in_flight) = state.in_flight.get_mut(&offset) {
+            if in_flight.member_id == member_id {
+                in_flight.delivered = true;
+            }
+        }
+    }
+
+    /// Commits messages delivered to the member with offsets up to `up_to` (inclusive)
+    /// or all delivered messages if `up_to` is `None`. Returns the committed offset of the group
+    ///
+    /// # Errors
+    /// If committed offset cannot be stored, function will return error from [fs::write]
+    pub async fn commit(&self, member_id: u64, up_to: Option<u64>) -> io::Result<u64> {
+        let committed = {
+            let mut state = self.state.lock().unwrap();
+            let processed: Vec<u64> = state
+                .in_flight
+                .iter()
+                .filter(|(offset, in_flight)| {
+                    in_flight.member_id == member_id
+                        && in_flight.delivered
+                        && up_to.is_none_or(|up_to| **offset <= up_to)
+                })
+                .map(|(offset, _)| *offset)
+                .collect();
+            Self::process(&mut state, processed)
+        };
+        self.store().await?;
+        Ok(committed)
+    }
+
+    /// Marks messages as processed without delivering them (e.g. they are no longer stored in the topic log)
+    ///
+    /// # Errors
+    /// If committed offset cannot be stored, function will return error from [fs::write]
+    pub async fn skip(&self, offsets: Vec<u64>) -> io::Result<u64> {
+        let committed = Self::process(&mut self.state.lock().unwrap(), offsets);
+        self.store().await?;
+        Ok(committed)
+    }
+
+    /// Marks messages as processed and returns the new committed offset
+    fn process(state: &mut GroupState, offsets: Vec<u64>) -> u64 {
+        for offset in offsets {
+            state.in_flight.remove(&offset);
+            state.processed.insert(offset);
+        }
+        loop {
+            let committed = state.committed;
+            if !state.processed.remove(&committed) {
+                break;
+            }
+            state.committed += 1;
+        }
+        state.committed
+    }
+
+    /// Writes the committed offset to the group file on a blocking thread, if it has changed.
+    /// Concurrent commits are written one by one, so the file never goes back to an older offset
+    async fn store(&self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        let mut stored = self.stored.lock().await;
+        let committed = self.committed();
+        if *stored == committed {
+            return Ok(());
+        }
+        spawn_blocking(move || {
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, committed.to_string())?;
+            fs::rename(tmp_path, path)
+        })
+        .await??;
+        *stored = committed;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_distribution() {
+        let group = ConsumerGroup::open("group".to_string(), None, 0).unwrap();
+        let (first, _) = group.join("topic");
+        let (second, _) = group.join("topic");
+
+        assert_eq!(group.claim(first, 3), Some(0));
+        assert_eq!(group.claim(second, 3), Some(1));
+        assert_eq!(group.claim(first, 3), Some(2));
+        assert_eq!(group.claim(second, 3), None);
+
+        group.delivered(first, 0);
+        group.delivered(second, 1);
+        group.delivered(first, 2);
+        assert_eq!(group.commit(second, None).await.unwrap(), 0);
+        assert_eq!(group.commit(first, Some(0)).await.unwrap(), 2);
+        assert_eq!(group.commit(first, None).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rebalance() {
+        let group = ConsumerGroup::open("group".to_string(), None, 5).unwrap();
+        let (first, _) = group.join("topic");
+        let (second, mut rebalances) = group.join("topic");
+
+        assert_eq!(group.claim(first, 7), Some(5));
+        assert_eq!(group.claim(first, 7), Some(6));
+        group.delivered(first, 5);
+        group.delivered(first, 6);
+        group.commit(first, Some(5)).await.unwrap();
+
+        rebalances.borrow_and_update();
+        group.leave("topic", first);
+        assert!(rebalances.has_changed().unwrap());
+
+        assert_eq!(group.claim(second, 7), Some(6));
+        assert_eq!(group.claim(second, 7), None);
+        group.delivered(second, 6);
+        assert_eq!(group.commit(second, None).await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_store() {
+        let path =
+            std::env::temp_dir().join(format!("simple-kafka-group-{}.group", std::process::id()));
+        let group = ConsumerGroup::open("group".to_string(), Some(path.clone()), 0).unwrap();
+        let (member, _) = group.join("topic");
+        assert_eq!(group.claim(member, 2), Some(0));
+        group.delivered(member, 0);
+        assert_eq!(group.commit(member, None).await.unwrap(), 1);
+        assert_eq!(group.skip(vec![1]).await.unwrap(), 2);
+        drop(group);
+
+        let group = ConsumerGroup::open("group".to_string(), Some(path.clone()), 0).unwrap();
+        assert_eq!(group.committed(), 2);
+        fs::remove_file(path).unwrap();
+    }
+}
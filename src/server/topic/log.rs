@@ -0,0 +1,361 @@
+use crate::server::topic::config::Retention;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const SEGMENT_EXTENSION: &str = "log";
+const RECORD_HEADER_SIZE: u64 = 4;
+
+/// Append-only log of the messages published to a `Topic`
+///
+/// The log is split into segments. On disk each segment is a `<base_offset>.log` file inside the
+/// topic directory, where `<base_offset>` is the offset of the first message in the segment.
+/// Every record is stored as a little-endian `u32` payload length followed by the payload itself
+///
+/// A log opened without a directory keeps its segments in memory and is lost when the `Server` stops
+pub(crate) struct TopicLog {
+    dir: Option<PathBuf>,
+    segments: Vec<Segment>,
+}
+
+impl TopicLog {
+    /// Opens the log stored in `dir` (creating it if needed) or a new in-memory log if `dir` is `None`
+    ///
+    /// A partially written record at the end of the last segment (e.g. after a crash) is truncated
+    ///
+    /// # Errors
+    /// All errors are inherited from [std::fs] operations on the log directory and segment files
+    pub fn open(dir: Option<PathBuf>) -> io::Result<Self> {
+        let mut segments = vec![];
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+            let mut base_offsets = vec![];
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|ext| ext != SEGMENT_EXTENSION) {
+                    continue;
+                }
+                if let Some(base_offset) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u64>().ok())
+                {
+                    base_offsets.push(base_offset);
+                }
+            }
+            base_offsets.sort_unstable();
+            for base_offset in base_offsets {
+                segments.push(Segment::open(dir, base_offset)?);
+            }
+        }
+        if segments.is_empty() {
+            segments.push(Segment::create(dir.as_deref(), 0)?);
+        }
+        Ok(Self { dir, segments })
+    }
+
+    /// Offset of the oldest message stored in the log
+    pub fn start_offset(&self) -> u64 {
+        self.segments[0].base_offset
+    }
+
+    /// Offset that will be assigned to the next appended message
+    pub fn next_offset(&self) -> u64 {
+        self.segments.last().unwrap().next_offset()
+    }
+
+    /// Number of segments of the log
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Total size of the log in bytes
+    pub fn size(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.size).sum()
+    }
+
+    /// Appends `payload` to the end of the log and returns its offset.
+    /// If the last segment is at least `segment_size` bytes, a new segment is started
+    ///
+    /// # Errors
+    /// All errors are inherited from [File::write_all]
+    pub fn append(&mut self, payload: &[u8], segment_size: u64) -> io::Result<u64> {
+        let last = self.segments.last().unwrap();
+        if last.size >= segment_size && !last.positions.is_empty() {
+            let segment = Segment::create(self.dir.as_deref(), last.next_offset())?;
+            self.segments.push(segment);
+        }
+
+        let segment = self.segments.last_mut().unwrap();
+        let offset = segment.next_offset();
+        segment.append(payload)?;
+        Ok(offset)
+    }
+
+    /// Removes the oldest segments exceeding `retention` limits. The last segment is never removed.
+    /// Returns the number of removed messages
+    ///
+    /// # Errors
+    /// All errors are inherited from [fs::remove_file]
+    pub fn apply_retention(&mut self, retention: &Retention) -> io::Result<u64> {
+        let mut size = self.size();
+        let expiration_time = retention
+            .max_age
+            .and_then(|max_age| SystemTime::now().checked_sub(Duration::from_secs(max_age)));
+
+        let mut removed = 0u64;
+        while self.segments.len() > 1 {
+            let oldest = &self.segments[0];
+            let exceeds_size = retention
+                .max_bytes
+                .is_some_and(|max_bytes| size > max_bytes);
+            let expired =
+                expiration_time.is_some_and(|expiration_time| oldest.last_append < expiration_time);
+            if !exceeds_size && !expired {
+                break;
+            }
+
+            let oldest = self.segments.remove(0);
+            if let Some(dir) = &self.dir {
+                fs::remove_file(Segment::path(dir, oldest.base_offset))?;
+            }
+            size -= oldest.size;
+            removed += oldest.positions.len() as u64;
+        }
+        Ok(removed)
+    }
+
+    /// Reads the message with `offset`. Returns `None` if there is no such message in the log
+    ///
+    /// # Errors
+    /// All errors are inherited from [File::read_exact]
+    pub fn read(&mut self, offset: u64) -> io::Result<Option<Vec<u8>>> {
+        if offset < self.start_offset() || offset >= self.next_offset() {
+            return Ok(None);
+        }
+        let index = self
+            .segments
+            .partition_point(|segment| segment.base_offset <= offset)
+            - 1;
+        self.segments[index].read(offset).map(Some)
+    }
+}
+
+enum SegmentData {
+    File(File),
+    Memory(Vec<u8>),
+}
+
+struct Segment {
+    base_offset: u64,
+    positions: Vec<u64>, // byte position of each record in the segment
+    size: u64,           // in bytes
+    last_append: SystemTime,
+    data: SegmentData,
+}
+
+impl Segment {
+    fn path(dir: &Path, base_offset: u64) -> PathBuf {
+        dir.join(format!("{:020}.{}", base_offset, SEGMENT_EXTENSION))
+    }
+
+    fn create(dir: Option<&Path>, base_offset: u64) -> io::Result<Self> {
+        let data = match dir {
+            Some(dir) => SegmentData::File(
+                OpenOptions::new()
+                    .read(true)
+                    .append(true)
+                    .create(true)
+                    .open(Self::path(dir, base_offset))?,
+            ),
+            None => SegmentData::Memory(vec![]),
+        };
+        Ok(Self {
+            base_offset,
+            positions: vec![],
+            size: 0,
+            last_append: SystemTime::now(),
+            data,
+        })
+    }
+
+    fn open(dir: &Path, base_offset: u64) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(Self::path(dir, base_offset))?;
+        let metadata = file.metadata()?;
+        let file_size = metadata.len();
+
+        let mut positions = vec![];
+        let mut size = 0u64;
+        let mut reader = BufReader::new(&file);
+        loop {
+            let mut header = [0u8; RECORD_HEADER_SIZE as usize];
+            match reader.read_exact(&mut header) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let length = u32::from_le_bytes(header) as u64;
+            if size + RECORD_HEADER_SIZE + length > file_size {
+                break;
+            }
+            reader.seek_relative(length as i64)?;
+            positions.push(size);
+            size += RECORD_HEADER_SIZE + length;
+        }
+
+        if size < file_size {
+            file.set_len(size)?; // cut off the partially written record
+        }
+
+        Ok(Self {
+            base_offset,
+            positions,
+            size,
+            last_append: metadata.modified()?,
+            data: SegmentData::File(file),
+        })
+    }
+
+    fn next_offset(&self) -> u64 {
+        self.base_offset + self.positions.len() as u64
+    }
+
+    fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE as usize + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(payload);
+
+        match &mut self.data {
+            SegmentData::File(file) => file.write_all(&record)?,
+            SegmentData::Memory(buffer) => buffer.extend_from_slice(&record),
+        }
+        self.positions.push(self.size);
+        self.size += record.len() as u64;
+        self.last_append = SystemTime::now();
+        Ok(())
+    }
+
+    fn read(&mut self, offset: u64) -> io::Result<Vec<u8>> {
+        let index = (offset - self.base_offset) as usize;
+        let start = self.positions[index] + RECORD_HEADER_SIZE;
+        let end = self.positions.get(index + 1).copied().unwrap_or(self.size);
+
+        match &mut self.data {
+            SegmentData::File(file) => {
+                let mut payload = vec![0u8; (end - start) as usize];
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut payload)?;
+                Ok(payload)
+            }
+            SegmentData::Memory(buffer) => Ok(buffer[start as usize..end as usize].to_vec()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    const SEGMENT_SIZE: u64 = 1024;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("simple-kafka-{}-{}", name, nanos))
+    }
+
+    #[test]
+    fn test_memory_log() {
+        let mut log = TopicLog::open(None).unwrap();
+        assert_eq!(log.append(b"first\n", SEGMENT_SIZE).unwrap(), 0);
+        assert_eq!(log.append(b"second\n", SEGMENT_SIZE).unwrap(), 1);
+        assert_eq!(log.next_offset(), 2);
+        assert_eq!(log.read(0).unwrap(), Some(b"first\n".to_vec()));
+        assert_eq!(log.read(1).unwrap(), Some(b"second\n".to_vec()));
+        assert_eq!(log.read(2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_reopen_log() {
+        let dir = temp_dir("reopen");
+        {
+            let mut log = TopicLog::open(Some(dir.clone())).unwrap();
+            log.append(b"first\n", SEGMENT_SIZE).unwrap();
+            log.append(b"second\n", SEGMENT_SIZE).unwrap();
+        }
+
+        let mut log = TopicLog::open(Some(dir.clone())).unwrap();
+        assert_eq!(log.next_offset(), 2);
+        assert_eq!(log.read(0).unwrap(), Some(b"first\n".to_vec()));
+        assert_eq!(log.append(b"third\n", SEGMENT_SIZE).unwrap(), 2);
+        assert_eq!(log.read(1).unwrap(), Some(b"second\n".to_vec()));
+        assert_eq!(log.read(2).unwrap(), Some(b"third\n".to_vec()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_truncate_partial_record() {
+        let dir = temp_dir("partial");
+        {
+            let mut log = TopicLog::open(Some(dir.clone())).unwrap();
+            log.append(b"complete\n", SEGMENT_SIZE).unwrap();
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(Segment::path(&dir, 0))
+            .unwrap();
+        file.write_all(&[100, 0, 0, 0, b'p']).unwrap();
+
+        let mut log = TopicLog::open(Some(dir.clone())).unwrap();
+        assert_eq!(log.next_offset(), 1);
+        assert_eq!(log.append(b"next\n", SEGMENT_SIZE).unwrap(), 1);
+        assert_eq!(log.read(1).unwrap(), Some(b"next\n".to_vec()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_retention() {
+        let dir = temp_dir("retention");
+        let mut log = TopicLog::open(Some(dir.clone())).unwrap();
+        for i in 0..10u8 {
+            log.append(&[i; 6], 20).unwrap(); // two records per segment
+        }
+        assert_eq!(log.size(), 100);
+
+        let retention = Retention {
+            max_bytes: Some(50),
+            max_age: None,
+        };
+        assert_eq!(log.apply_retention(&retention).unwrap(), 6);
+        assert_eq!(log.start_offset(), 6);
+        assert_eq!(log.read(5).unwrap(), None);
+        assert_eq!(log.read(6).unwrap(), Some(vec![6; 6]));
+
+        let log = TopicLog::open(Some(dir.clone())).unwrap();
+        assert_eq!(log.start_offset(), 6);
+        assert_eq!(log.next_offset(), 10);
+
+        let mut log = TopicLog::open(None).unwrap();
+        for i in 0..10u8 {
+            log.append(&[i; 6], 20).unwrap();
+        }
+        let retention = Retention {
+            max_bytes: None,
+            max_age: Some(0),
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(log.apply_retention(&retention).unwrap(), 8);
+        assert_eq!(log.start_offset(), 8);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
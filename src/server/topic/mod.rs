@@ -0,0 +1,380 @@
+pub mod config;
+pub(crate) mod filter;
+pub(crate) mod group;
+pub(crate) mod log;
+pub(crate) mod record;
+
+use crate::server::metrics::TopicMetrics;
+use crate::server::topic::config::{SlowConsumerPolicy, TopicConfig};
+use crate::server::topic::group::ConsumerGroup;
+use crate::server::topic::log::TopicLog;
+pub(crate) use crate::server::topic::record::{Record, RecordMetadata};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast::{channel, Receiver, Sender};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::spawn_blocking;
+
+const TOPIC_DIR_PREFIX: &str = "topic-";
+const GROUP_FILE_EXTENSION: &str = "group";
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Result of [Topic::fetch]
+pub(crate) enum Fetch {
+    /// The message stored at the requested offset
+    Record(Record),
+    /// The requested offset has not been published yet.
+    /// The receiver gets every message published after the fetch
+    UpToDate(Receiver<Record>),
+}
+
+/// Positions of the subscribers receiving messages in real time.
+/// Used to block publishers with [SlowConsumerPolicy::Block]
+#[derive(Default)]
+struct Cursors {
+    positions: std::sync::Mutex<HashMap<u64, u64>>,
+    next_id: AtomicU64,
+    moved: Notify,
+}
+
+/// Position of a subscriber in [Cursors]. It is removed when dropped
+pub(crate) struct Cursor {
+    cursors: Arc<Cursors>,
+    id: u64,
+}
+
+impl Cursor {
+    /// Moves the cursor to `position` and wakes up waiting publishers
+    pub fn update(&self, position: u64) {
+        self.cursors
+            .positions
+            .lock()
+            .unwrap()
+            .insert(self.id, position);
+        self.cursors.moved.notify_waiters();
+    }
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        self.cursors.positions.lock().unwrap().remove(&self.id);
+        self.cursors.moved.notify_waiters();
+    }
+}
+
+pub(crate) struct Topic {
+    pub name: String,
+    config: Arc<RwLock<TopicConfig>>,
+    pub sender: Sender<Record>,
+    channel_capacity: usize,
+    dir: Option<PathBuf>,
+    log: Arc<Mutex<TopicLog>>,
+    groups: Arc<Mutex<HashMap<String, Arc<ConsumerGroup>>>>,
+    cursors: Arc<Cursors>,
+    pub metrics: Arc<TopicMetrics>,
+}
+
+impl Clone for Topic {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            config: self.config.clone(),
+            sender: self.sender.clone(),
+            channel_capacity: self.channel_capacity,
+            dir: self.dir.clone(),
+            log: self.log.clone(),
+            groups: self.groups.clone(),
+            cursors: self.cursors.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl Topic {
+    /// Opens topic `topic_name` with its log stored inside `data_dir`.
+    /// Without `data_dir` the topic log is kept in memory
+    ///
+    /// `config` is used only for a new topic, an existing topic keeps its stored config
+    ///
+    /// # Errors
+    /// All errors are inherited from [TopicLog::open] and [TopicConfig::load]/[TopicConfig::store]
+    pub fn open(
+        topic_name: String,
+        data_dir: Option<&Path>,
+        config: &TopicConfig,
+    ) -> io::Result<Self> {
+        let dir = data_dir.map(|data_dir| Self::dir(data_dir, &topic_name));
+        let mut log = TopicLog::open(dir.clone())?;
+        let config = match &dir {
+            Some(dir) => match TopicConfig::load(&dir.join(CONFIG_FILE_NAME))? {
+                Some(stored_config) => stored_config,
+                None => {
+                    config.store(&dir.join(CONFIG_FILE_NAME))?;
+                    config.clone()
+                }
+            },
+            None => config.clone(),
+        };
+        log.apply_retention(&config.retention)?;
+        let channel_capacity = config.channel_capacity;
+        let (sender, _) = channel(channel_capacity);
+        Ok(Self {
+            name: topic_name,
+            config: Arc::new(RwLock::new(config)),
+            sender,
+            channel_capacity,
+            dir,
+            log: Arc::new(Mutex::new(log)),
+            groups: Arc::new(Mutex::new(HashMap::default())),
+            cursors: Arc::new(Cursors::default()),
+            metrics: Arc::new(TopicMetrics::default()),
+        })
+    }
+
+    /// Current config of the topic
+    pub fn config(&self) -> TopicConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Replaces config of the topic and stores it together with the topic log
+    ///
+    /// `channel_capacity` of the running topic does not change until the topic is opened again
+    ///
+    /// # Errors
+    /// All errors are inherited from [TopicConfig::store]
+    pub fn set_config(&self, config: TopicConfig) -> io::Result<()> {
+        if let Some(dir) = &self.dir {
+            config.store(&dir.join(CONFIG_FILE_NAME))?;
+        }
+        *self.config.write().unwrap() = config;
+        Ok(())
+    }
+
+    /// Removes the topic log, config and consumer groups from `data_dir`.
+    /// The topic must not be used afterwards
+    ///
+    /// # Errors
+    /// All errors are inherited from [std::fs::remove_dir_all]
+    pub async fn delete(&self) -> io::Result<()> {
+        let _log = self.log.lock().await; // waits for the message being appended
+        match self.dir.clone() {
+            Some(dir) => spawn_blocking(move || std::fs::remove_dir_all(dir)).await?,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns names of all topics stored in `data_dir`
+    ///
+    /// # Errors
+    /// All errors are inherited from [std::fs::read_dir]
+    pub fn stored_names(data_dir: &Path) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        if !data_dir.exists() {
+            return Ok(names);
+        }
+        for entry in std::fs::read_dir(data_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry
+                .file_name()
+                .to_str()
+                .and_then(|dir_name| dir_name.strip_prefix(TOPIC_DIR_PREFIX))
+                .and_then(decode_hex)
+            {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Directory of the topic log. Topic name is hex-encoded, so any name is a valid directory name
+    fn dir(data_dir: &Path, topic_name: &str) -> PathBuf {
+        data_dir.join(format!("{}{}", TOPIC_DIR_PREFIX, encode_hex(topic_name)))
+    }
+
+    /// Returns consumer group `group_name` of the topic, loading it if needed.
+    /// A group without stored committed offset starts from `start_offset`
+    ///
+    /// # Errors
+    /// All errors are inherited from [ConsumerGroup::open]
+    pub async fn group(
+        &self,
+        group_name: &str,
+        start_offset: u64,
+    ) -> io::Result<Arc<ConsumerGroup>> {
+        let mut groups = self.groups.lock().await;
+        if let Some(group) = groups.get(group_name) {
+            return Ok(group.clone());
+        }
+        let path = self.dir.as_ref().map(|dir| {
+            dir.join(format!(
+                "{}.{}",
+                encode_hex(group_name),
+                GROUP_FILE_EXTENSION
+            ))
+        });
+        let group = Arc::new(ConsumerGroup::open(
+            group_name.to_string(),
+            path,
+            start_offset,
+        )?);
+        groups.insert(group_name.to_string(), group.clone());
+        Ok(group)
+    }
+
+    /// Offsets of the oldest stored message and of the next message to be published
+    pub async fn offsets(&self) -> (u64, u64) {
+        let log = self.log.lock().await;
+        (log.start_offset(), log.next_offset())
+    }
+
+    /// Appends `message` with its `metadata` to the topic log and only then sends it to the connected subscribers.
+    /// Retention of the topic is applied when the message starts a new segment
+    ///
+    /// Logs stored on disk are written on a blocking thread, the log stays locked until the message is sent
+    ///
+    /// Returns the offset assigned to the message
+    ///
+    /// # Errors
+    /// All errors are inherited from [TopicLog::append] and [TopicLog::apply_retention]
+    pub async fn publish(&self, metadata: RecordMetadata, message: Vec<u8>) -> io::Result<u64> {
+        let config = self.config();
+        if config.slow_consumer_policy == SlowConsumerPolicy::Block {
+            self.wait_for_slow_subscribers().await;
+        }
+
+        let mut log = self.log.clone().lock_owned().await;
+        let sender = self.sender.clone();
+        let append = move || {
+            let mut record = Record {
+                offset: log.next_offset(),
+                metadata,
+                payload: message,
+            };
+            let segment_count = log.segment_count();
+            record.offset = log.append(&record.encode(), config.segment_size)?;
+            if log.segment_count() > segment_count {
+                log.apply_retention(&config.retention)?;
+            }
+            let offset = record.offset;
+            let _ = sender.send(record); // there may be no subscribers, the message is stored anyway
+            Ok(offset)
+        };
+        match self.dir {
+            Some(_) => spawn_blocking(append).await?,
+            None => append(),
+        }
+    }
+
+    /// Waits until every subscriber receiving messages in real time
+    /// is less than the channel capacity behind the topic log
+    async fn wait_for_slow_subscribers(&self) {
+        loop {
+            let moved = self.cursors.moved.notified();
+            tokio::pin!(moved);
+            moved.as_mut().enable();
+
+            let next_offset = self.log.lock().await.next_offset();
+            let slowest = self
+                .cursors
+                .positions
+                .lock()
+                .unwrap()
+                .values()
+                .min()
+                .copied();
+            match slowest {
+                // a cursor ahead of the log is not behind at all
+                Some(position)
+                    if next_offset.saturating_sub(position) >= self.channel_capacity as u64 =>
+                {
+                    moved.await
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Registers position of a subscriber receiving messages in real time
+    pub fn cursor(&self, position: u64) -> Cursor {
+        let cursor = Cursor {
+            cursors: self.cursors.clone(),
+            id: self.cursors.next_id.fetch_add(1, Ordering::Relaxed),
+        };
+        cursor.update(position);
+        cursor
+    }
+
+    /// Reads the message stored at `offset`. Returns `None` if there is no such message in the log
+    ///
+    /// # Errors
+    /// All errors are inherited from [TopicLog::read] and [Record::decode]
+    pub async fn read(&self, offset: u64) -> io::Result<Option<Record>> {
+        let mut log = self.log.lock().await;
+        log.read(offset)?
+            .map(|data| Record::decode(offset, data))
+            .transpose()
+    }
+
+    /// Reads the message stored at `offset` or, if it is not published yet, subscribes to new messages.
+    /// Offsets of messages that are no longer stored are moved to the oldest stored message
+    ///
+    /// Both happen under the log lock, so no message is missed between reading the log and subscribing
+    ///
+    /// # Errors
+    /// All errors are inherited from [TopicLog::read] and [Record::decode]
+    pub async fn fetch(&self, offset: u64) -> io::Result<Fetch> {
+        let mut log = self.log.lock().await;
+        let offset = offset.max(log.start_offset());
+        match log.read(offset)? {
+            Some(data) => Ok(Fetch::Record(Record::decode(offset, data)?)),
+            None => Ok(Fetch::UpToDate(self.sender.subscribe())),
+        }
+    }
+}
+
+fn encode_hex(text: &str) -> String {
+    text.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(encoded: &str) -> Option<String> {
+    if !encoded.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..encoded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(encoded.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_cursor_after_log_end() {
+        let config = TopicConfig {
+            slow_consumer_policy: SlowConsumerPolicy::Block,
+            channel_capacity: 1,
+            ..TopicConfig::default()
+        };
+        let topic = Topic::open("events".to_string(), None, &config).unwrap();
+        let _cursor = topic.cursor(1_000_000);
+        for offset in 0..3 {
+            let published = timeout(
+                Duration::from_secs(1),
+                topic.publish(RecordMetadata::default(), b"message".to_vec()),
+            )
+            .await;
+            assert_eq!(published.unwrap().unwrap(), offset);
+        }
+    }
+}
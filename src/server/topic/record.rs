@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const METADATA_LENGTH_SIZE: usize = 4;
+
+/// What the `Server` knows about a message besides its payload
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, Clone)]
+#[serde(default)]
+pub(crate) struct RecordMetadata {
+    /// Unix time (in seconds) when the message was published
+    pub time: u64,
+    /// Connection id of the publisher
+    pub connection_id: u64,
+    /// Name of the publisher
+    pub publisher_name: Option<String>,
+    /// Key of the message set by the publisher
+    pub key: Option<String>,
+    /// Headers of the message set by the publisher
+    pub headers: HashMap<String, String>,
+}
+
+impl RecordMetadata {
+    /// Metadata of a message published by the client with `connection_id` right now
+    pub fn new(connection_id: u64, publisher_name: Option<String>) -> Self {
+        Self {
+            time: unix_time(),
+            connection_id,
+            publisher_name,
+            key: None,
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// Current Unix time in seconds
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// A message stored in the topic log together with its offset
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Record {
+    pub offset: u64,
+    pub metadata: RecordMetadata,
+    pub payload: Vec<u8>,
+}
+
+impl Record {
+    /// Encodes the record to be appended to the topic log:
+    /// little-endian `u32` metadata length, metadata in JSON format and the payload
+    pub fn encode(&self) -> Vec<u8> {
+        let metadata = serde_json::to_vec(&self.metadata).unwrap();
+        let mut data =
+            Vec::with_capacity(METADATA_LENGTH_SIZE + metadata.len() + self.payload.len());
+        data.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        data.extend_from_slice(&metadata);
+        data.extend_from_slice(&self.payload);
+        data
+    }
+
+    /// Decodes the record read from the topic log at `offset`
+    ///
+    /// # Errors
+    /// If `data` is not an encoded record, function will return [ErrorKind::InvalidData]
+    pub fn decode(offset: u64, mut data: Vec<u8>) -> io::Result<Self> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("corrupted record {}", offset),
+            )
+        };
+        let length: [u8; METADATA_LENGTH_SIZE] = data
+            .get(..METADATA_LENGTH_SIZE)
+            .and_then(|length| length.try_into().ok())
+            .ok_or_else(invalid)?;
+        let metadata_end = METADATA_LENGTH_SIZE + u32::from_le_bytes(length) as usize;
+        let metadata = data
+            .get(METADATA_LENGTH_SIZE..metadata_end)
+            .and_then(|metadata| serde_json::from_slice(metadata).ok())
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            offset,
+            metadata,
+            payload: data.split_off(metadata_end),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let record = Record {
+            offset: 7,
+            metadata: RecordMetadata {
+                key: Some("user-42".to_string()),
+                headers: HashMap::from([("trace_id".to_string(), "a1b2".to_string())]),
+                ..RecordMetadata::new(5, Some("sensor".to_string()))
+            },
+            payload: b"binary\n\0payload".to_vec(),
+        };
+        assert_eq!(Record::decode(7, record.encode()).unwrap(), record);
+
+        assert!(Record::decode(7, vec![10, 0, 0, 0, b'{', b'}']).is_err());
+        assert!(Record::decode(7, vec![]).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use {
+    crate::server::stream::PeerCredentials,
+    log::warn,
+    std::io::ErrorKind,
+    std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    std::path::Path,
+    std::{fs, io},
+    tokio::net::{UnixListener, UnixStream},
+};
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+/// Unix domain socket on which the `Server` accepts clients running on the same host
+///
+/// ```TOML
+/// [unix_listener]
+/// path = "/run/simple-kafka/simple-kafka.sock"
+/// mode = 0o660
+/// ```
+///
+/// - `path` - path of the socket file. A stale socket file left by a stopped `Server` is removed at startup
+/// - `mode` - permissions of the socket file, set by the umask of the `Server` by default
+///
+/// Clients connected over the socket are identified by the credentials of their process.
+/// Clients without credentials are authenticated as `uid:<uid>` of the process,
+/// see [AuthConfig](crate::server::auth::AuthConfig)
+pub struct UnixListenerConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub mode: Option<u32>,
+}
+
+impl UnixListenerConfig {
+    /// Checks that all values can be used
+    ///
+    /// # Errors
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.path.as_os_str().is_empty() {
+            return Err("unix_listener: path is empty".to_string());
+        }
+        if let Some(mode) = self.mode.filter(|mode| *mode > 0o777) {
+            return Err(format!(
+                "unix_listener: mode {:o} is not a permission mode",
+                mode
+            ));
+        }
+        Ok(())
+    }
+
+    /// Binds the socket, removing a stale socket file first
+    ///
+    /// # Errors
+    /// - If the socket is used by a running `Server`, function will return [ErrorKind::AddrInUse]
+    /// - If the path exists and is not a socket, function will return [ErrorKind::AlreadyExists]
+    /// - All other errors are inherited from [UnixListener::bind] and [fs] functions
+    #[cfg(unix)]
+    pub(crate) async fn bind(&self) -> io::Result<UnixSocketListener> {
+        let in_socket = |e: io::Error| {
+            io::Error::new(e.kind(), format!("socket {} -- {}", self.path.display(), e))
+        };
+        remove_stale_socket(&self.path).await.map_err(in_socket)?;
+        let listener = match self.mode {
+            Some(mode) => self.bind_with_mode(mode),
+            None => UnixListener::bind(&self.path),
+        }
+        .map_err(in_socket)?;
+        Ok(UnixSocketListener {
+            listener,
+            path: self.path.clone(),
+        })
+    }
+
+    /// Binds the socket in a private directory next to `path`, sets its permissions to `mode`
+    /// and only then moves it to `path`, so that the socket is never accessible with the permissions of the umask
+    #[cfg(unix)]
+    fn bind_with_mode(&self, mode: u32) -> io::Result<UnixListener> {
+        let file_name = self
+            .path
+            .file_name()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "the path has no file name"))?;
+        let private_dir = self.path.with_file_name(format!(
+            ".{}.{}",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+        fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+        let private_path = private_dir.join(file_name);
+        let listener = UnixListener::bind(&private_path).and_then(|listener| {
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(mode))?;
+            fs::rename(&private_path, &self.path)?;
+            Ok(listener)
+        });
+        let _ = fs::remove_file(&private_path); // left only if the socket was not moved
+        let _ = fs::remove_dir(&private_dir);
+        listener
+    }
+}
+
+/// Unix domain socket bound by [UnixListenerConfig::bind]. The socket file is removed when it is dropped
+#[cfg(unix)]
+pub(crate) struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketListener {
+    /// Accepts the next client together with the credentials of its process
+    ///
+    /// # Errors
+    /// All errors are inherited from [UnixListener::accept] and [UnixStream::peer_cred]
+    pub async fn accept(&self) -> io::Result<(UnixStream, PeerCredentials)> {
+        let (stream, _) = self.listener.accept().await?;
+        let credentials = PeerCredentials::of(&stream)?;
+        Ok((stream, credentials))
+    }
+
+    /// Path of the socket file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Removes the socket file at `path` if no `Server` accepts connections on it
+#[cfg(unix)]
+async fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            "the path exists and is not a socket",
+        ));
+    }
+    match UnixStream::connect(path).await {
+        Ok(_) => Err(io::Error::new(
+            ErrorKind::AddrInUse,
+            "the socket is used by a running server",
+        )),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+            warn!("Removing stale socket {}", path.display());
+            fs::remove_file(path)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let config = UnixListenerConfig {
+            path: PathBuf::from("/run/simple-kafka.sock"),
+            mode: Some(0o660),
+        };
+        assert!(config.validate().is_ok());
+        assert!(UnixListenerConfig {
+            mode: Some(0o1777),
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(UnixListenerConfig {
+            path: PathBuf::new(),
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind() {
+        let dir = std::env::temp_dir().join(format!("simple-kafka-unix-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = UnixListenerConfig {
+            path: dir.join("kafka.sock"),
+            mode: Some(0o600),
+        };
+
+        // a socket file left by a stopped server
+        drop(std::os::unix::net::UnixListener::bind(&config.path).unwrap());
+        let listener = config.bind().await.unwrap();
+        assert_eq!(
+            fs::metadata(&config.path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        // the private directory of the socket is removed
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        drop(tokio::net::UnixStream::connect(&config.path).await.unwrap());
+        assert_eq!(
+            config.bind().await.err().map(|e| e.kind()),
+            Some(ErrorKind::AddrInUse)
+        );
+        drop(listener);
+        assert!(!config.path.exists());
+
+        fs::write(&config.path, "not a socket").unwrap();
+        assert_eq!(
+            config.bind().await.err().map(|e| e.kind()),
+            Some(ErrorKind::AlreadyExists)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
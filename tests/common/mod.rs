@@ -0,0 +1,4 @@
+pub mod message;
+pub mod publisher;
+pub mod subscriber;
+pub mod utils;
@@ -0,0 +1,126 @@
+use super::message::MessageQueue;
+use super::utils::Stream;
+use serde_json::{json, Map, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+pub struct Publisher {
+    stream: BufReader<Box<dyn Stream>>,
+}
+
+impl Publisher {
+    pub async fn new(server_address: &SocketAddr) -> Self {
+        Self::with_stream(
+            TcpStream::connect(server_address)
+                .await
+                .unwrap_or_else(|_| panic!("Cannot connect to server {}", server_address)),
+        )
+    }
+
+    /// Publishes over an already connected `stream`
+    pub fn with_stream(stream: impl Stream + 'static) -> Self {
+        Self {
+            stream: BufReader::new(Box::new(stream)),
+        }
+    }
+
+    async fn auth(&mut self, topic: String, delimiter: u8) -> bool {
+        self.auth_with_options(topic, &Map::new(), delimiter).await
+    }
+
+    pub async fn auth_with_options(
+        &mut self,
+        topic: String,
+        options: &Map<String, Value>,
+        delimiter: u8,
+    ) -> bool {
+        let mut connection_message = json!({
+            "method": "publish",
+            "topic": topic
+        });
+        connection_message
+            .as_object_mut()
+            .unwrap()
+            .extend(options.clone());
+        let mut message_bytes: Vec<u8> = connection_message.to_string().as_bytes().to_vec();
+        message_bytes.push(delimiter);
+        if self
+            .stream
+            .write_all(message_bytes.as_slice())
+            .await
+            .is_err()
+        {
+            return false;
+        }
+        true
+    }
+
+    pub async fn send_messages(
+        &mut self,
+        topic: String,
+        message_queue: MessageQueue,
+        delimiter: u8,
+    ) -> bool {
+        if !self.auth(topic, delimiter).await {
+            return false;
+        }
+
+        let mut previous_expected_sent_time: f64 = 0.0;
+        for message in &message_queue.messages {
+            tokio::time::sleep(Duration::from_secs_f64(
+                message.expected_sent_time - previous_expected_sent_time,
+            ))
+            .await;
+            let mut message_bytes = message.message.as_bytes().to_vec();
+            message_bytes.push(delimiter);
+            if self
+                .stream
+                .write_all(message_bytes.as_slice())
+                .await
+                .is_err()
+            {
+                return false;
+            }
+            previous_expected_sent_time = message.expected_sent_time;
+        }
+        true
+    }
+
+    pub async fn send_message(&mut self, message: Value, delimiter: u8) -> bool {
+        let mut message_bytes: Vec<u8> = message.to_string().as_bytes().to_vec();
+        message_bytes.push(delimiter);
+        self.stream
+            .write_all(message_bytes.as_slice())
+            .await
+            .is_ok()
+    }
+
+    /// Sends several lines in one write
+    pub async fn send_lines(&mut self, lines: &[&str], delimiter: u8) -> bool {
+        let mut message_bytes: Vec<u8> = vec![];
+        for line in lines {
+            message_bytes.extend_from_slice(line.as_bytes());
+            message_bytes.push(delimiter);
+        }
+        self.stream
+            .write_all(message_bytes.as_slice())
+            .await
+            .is_ok()
+    }
+
+    /// Reads a reply of the server
+    pub async fn read_reply(&mut self, delimiter: u8) -> Option<Value> {
+        let mut buffer: Vec<u8> = vec![];
+        self.stream.read_until(delimiter, &mut buffer).await.ok()?;
+        serde_json::from_slice(&buffer).ok()
+    }
+
+    /// Sends a length-prefixed frame
+    pub async fn send_frame(&mut self, payload: &[u8]) -> bool {
+        let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(payload);
+        self.stream.write_all(frame.as_slice()).await.is_ok()
+    }
+}
@@ -0,0 +1,188 @@
+use super::message::MessageQueue;
+use super::utils::{current_time, Stream};
+use serde_json::{json, Map, Value};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const GL_EXTRA_TIMEOUT: f64 = 2.0;
+
+pub struct Subscriber {
+    buf_reader: BufReader<Box<dyn Stream>>,
+}
+
+impl Subscriber {
+    pub async fn new(server_address: &SocketAddr) -> Self {
+        let stream = TcpStream::connect(server_address)
+            .await
+            .unwrap_or_else(|_| panic!("Cannot connect to server {}", server_address));
+        Self::with_stream(stream)
+    }
+
+    /// Subscribes over an already connected `stream`
+    pub fn with_stream(stream: impl Stream + 'static) -> Self {
+        Self {
+            buf_reader: BufReader::new(Box::new(stream)),
+        }
+    }
+
+    pub async fn auth(
+        &mut self,
+        topic: String,
+        options: &Map<String, Value>,
+        delimiter: u8,
+    ) -> bool {
+        let mut connection_message = json!({
+            "method": "subscribe",
+            "topic": topic
+        });
+        connection_message
+            .as_object_mut()
+            .unwrap()
+            .extend(options.clone());
+        let mut message_bytes: Vec<u8> = connection_message.to_string().as_bytes().to_vec();
+        message_bytes.push(delimiter);
+        if self
+            .buf_reader
+            .write_all(message_bytes.as_slice())
+            .await
+            .is_err()
+        {
+            return false;
+        }
+        true
+    }
+
+    pub async fn send_message(&mut self, message: Value, delimiter: u8) -> bool {
+        let mut message_bytes: Vec<u8> = message.to_string().as_bytes().to_vec();
+        message_bytes.push(delimiter);
+        self.buf_reader
+            .write_all(message_bytes.as_slice())
+            .await
+            .is_ok()
+    }
+
+    /// Reads messages until no new message is received within `wait_time` seconds
+    pub async fn read_messages(&mut self, delimiter: u8, wait_time: f64) -> Vec<String> {
+        let mut messages = vec![];
+        while let Ok(Some(message)) = timeout(
+            Duration::from_secs_f64(wait_time),
+            self.read_message(delimiter),
+        )
+        .await
+        {
+            match message.strip_suffix("\n") {
+                Some(message) => messages.push(message.to_string()),
+                None => break,
+            }
+        }
+        messages
+    }
+
+    /// Reads a length-prefixed frame, waiting for it no longer than `wait_time` seconds
+    pub async fn read_frame(&mut self, wait_time: f64) -> Option<Vec<u8>> {
+        timeout(Duration::from_secs_f64(wait_time), async {
+            let length = self.buf_reader.read_u32().await.ok()?;
+            let mut payload = vec![0u8; length as usize];
+            self.buf_reader.read_exact(&mut payload).await.ok()?;
+            Some(payload)
+        })
+        .await
+        .ok()?
+    }
+
+    /// Reads the error sent by the server before closing the connection and returns its code
+    pub async fn read_error(&mut self, delimiter: u8) -> Option<String> {
+        let message: Value = serde_json::from_str(&self.read_message(delimiter).await?).ok()?;
+        if message["type"] != "error" || self.wait_message().await {
+            return None;
+        }
+        message["data"]["code"].as_str().map(String::from)
+    }
+
+    pub async fn wait_message(&mut self) -> bool {
+        let mut buf = [0u8; 1];
+        match self.buf_reader.read(&mut buf).await {
+            Ok(sz) => sz > 0usize,
+            Err(_) => false,
+        }
+    }
+
+    pub async fn read_message(&mut self, delimiter: u8) -> Option<String> {
+        let mut buffer: Vec<u8> = vec![];
+
+        match self.buf_reader.read_until(delimiter, &mut buffer).await {
+            Ok(_) => Some(String::from_utf8_lossy(buffer.as_slice()).into_owned()),
+            Err(_) => None,
+        }
+    }
+}
+
+pub struct SubscriberTester {
+    subscriber: Subscriber,
+    delimiter: u8,
+    options: Map<String, Value>,
+}
+
+impl SubscriberTester {
+    pub fn new(subscriber: Subscriber, delimiter: u8) -> Self {
+        Self {
+            subscriber,
+            delimiter,
+            options: Map::new(),
+        }
+    }
+
+    /// Adds `key` field to the connection message
+    pub fn with_option(mut self, key: &str, value: Value) -> Self {
+        self.options.insert(key.to_string(), value);
+        self
+    }
+
+    pub async fn test_messages(
+        &mut self,
+        topic: String,
+        mut message_queue: MessageQueue,
+        after_all_timeout: f64, /* in seconds */
+    ) -> bool {
+        if !self
+            .subscriber
+            .auth(topic, &self.options, self.delimiter)
+            .await
+        {
+            return false;
+        }
+
+        while !message_queue.is_empty() {
+            let timout_sec = message_queue.start_time
+                + GL_EXTRA_TIMEOUT
+                + message_queue.current_message().expected_sent_time
+                - current_time();
+
+            match timeout(
+                Duration::from_secs_f64(timout_sec),
+                self.subscriber.read_message(self.delimiter),
+            )
+            .await
+            {
+                Ok(message) => {
+                    if message.is_none()
+                        || !message_queue.new_message(message.unwrap().strip_suffix("\n").unwrap())
+                    {
+                        return false;
+                    }
+                }
+                Err(_) => return false,
+            }
+        }
+
+        timeout(
+            Duration::from_secs_f64(after_all_timeout),
+            self.subscriber.wait_message(),
+        )
+        .await
+        .map_or(true, |has_message| !has_message)
+    }
+}
@@ -1,13 +1,19 @@
-mod message;
-mod publisher;
-mod subscriber;
-pub mod utils;
+mod common;
 
-use crate::message::Message;
-use simple_kafka::Server;
+use common::message::Message;
+use common::{message, publisher, subscriber, utils};
 use log::LevelFilter::Debug;
 use publisher::Publisher;
+use serde_json::{json, Map, Value};
+use simple_kafka::{
+    hash_password, AclConfig, AclDefault, AclRule, AuthConfig, Limits, ListenerConfig, Permission,
+    Server, ServerConfig, ShutdownHandle, TlsConfig, TopicConfig, TopicsConfig, UnixListenerConfig,
+};
+use std::collections::{BTreeMap, HashMap};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use subscriber::{Subscriber, SubscriberTester};
 use tokio::io;
 use tokio::join;
@@ -40,24 +46,190 @@ mod integration_tests {
         SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0)
     }
 
+    fn gen_data_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "simple-kafka-{}-{}",
+            name,
+            utils::current_time().to_bits()
+        ))
+    }
+
     async fn create_server(server_details: SocketAddr) -> Server {
         Server::new(&server_details)
             .await
-            .expect(format!("Unable to start server at {}", server_details).as_str())
+            .unwrap_or_else(|_| panic!("Unable to start server at {}", server_details))
+    }
+
+    /// Access control lists granting [Permission::Admin] to `principal`, everything else is allowed
+    fn admin_acl(principal: &str) -> AclConfig {
+        AclConfig {
+            default: AclDefault::Allow,
+            rules: vec![AclRule {
+                principal: principal.to_string(),
+                topics: vec![],
+                permissions: vec![Permission::Admin],
+            }],
+        }
+    }
+
+    struct ServerTask {
+        task: JoinHandle<io::Result<()>>,
+        shutdown: ShutdownHandle,
+    }
+
+    impl ServerTask {
+        /// Shuts the server down and waits until it is stopped
+        async fn stop(self) {
+            self.shutdown.shutdown();
+            let result = self.task.await;
+            assert!(result.is_ok() && result.unwrap().is_ok());
+        }
+    }
+
+    async fn run_server(mut server: Server) -> ServerTask {
+        let shutdown = server.shutdown_handle();
+        let task = tokio::spawn(async move { server.run().await });
+        ServerTask { task, shutdown }
     }
-    async fn run_server(mut server: Server) -> JoinHandle<io::Result<()>> {
-        tokio::spawn(async move { server.run().await })
+
+    /// Sends a GET request to the HTTP endpoint and returns the status line and the body
+    async fn http_get(http_port: u16, path: &str) -> (String, String) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), http_port);
+        let mut stream = tokio::net::TcpStream::connect(socket_addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    /// Certificate authority issuing certificates of the server and clients, stored in `dir`
+    struct TestPki {
+        dir: PathBuf,
+        ca_certificate: rcgen::Certificate,
+        ca_key: rcgen::KeyPair,
+    }
+
+    impl TestPki {
+        fn new(name: &str) -> Self {
+            use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+
+            let dir = gen_data_dir(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            let mut params = CertificateParams::new(vec![]).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "simple-kafka test CA");
+            let ca_key = KeyPair::generate().unwrap();
+            let ca_certificate = params.self_signed(&ca_key).unwrap();
+            std::fs::write(dir.join("ca.pem"), ca_certificate.pem()).unwrap();
+            Self {
+                dir,
+                ca_certificate,
+                ca_key,
+            }
+        }
+
+        /// Issues a certificate of `common_name` for `purpose`
+        fn issue(
+            &self,
+            common_name: &str,
+            purpose: rcgen::ExtendedKeyUsagePurpose,
+        ) -> (rcgen::Certificate, rcgen::KeyPair) {
+            use rcgen::{CertificateParams, DnType, KeyPair};
+
+            let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+            params
+                .distinguished_name
+                .push(DnType::CommonName, common_name);
+            params.extended_key_usages = vec![purpose];
+            let key = KeyPair::generate().unwrap();
+            let certificate = params
+                .signed_by(&key, &self.ca_certificate, &self.ca_key)
+                .unwrap();
+            (certificate, key)
+        }
+
+        /// Stores a server certificate and returns the config of the server requiring client certificates
+        fn server_config(&self) -> TlsConfig {
+            let (certificate, key) =
+                self.issue("localhost", rcgen::ExtendedKeyUsagePurpose::ServerAuth);
+            std::fs::write(self.dir.join("server.pem"), certificate.pem()).unwrap();
+            std::fs::write(self.dir.join("server.key"), key.serialize_pem()).unwrap();
+            TlsConfig {
+                cert_file: self.dir.join("server.pem"),
+                key_file: self.dir.join("server.key"),
+                client_ca_file: Some(self.dir.join("ca.pem")),
+                require_client_cert: true,
+            }
+        }
+
+        /// Connects to the server over TLS with a client certificate of `common_name`, if any
+        async fn connect(
+            &self,
+            server_address: &SocketAddr,
+            common_name: Option<&str>,
+        ) -> tokio_rustls::client::TlsStream<tokio::net::TcpStream> {
+            use tokio_rustls::rustls::pki_types::{PrivateKeyDer, ServerName};
+            use tokio_rustls::rustls::{crypto, ClientConfig, RootCertStore};
+
+            let mut roots = RootCertStore::empty();
+            roots.add(self.ca_certificate.der().clone()).unwrap();
+            let builder =
+                ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                    .with_safe_default_protocol_versions()
+                    .unwrap()
+                    .with_root_certificates(roots);
+            let config = match common_name {
+                Some(common_name) => {
+                    let (certificate, key) =
+                        self.issue(common_name, rcgen::ExtendedKeyUsagePurpose::ClientAuth);
+                    builder
+                        .with_client_auth_cert(
+                            vec![certificate.der().clone()],
+                            PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+                        )
+                        .unwrap()
+                }
+                None => builder.with_no_client_auth(),
+            };
+            let stream = tokio::net::TcpStream::connect(server_address)
+                .await
+                .unwrap();
+            tokio_rustls::TlsConnector::from(Arc::new(config))
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await
+                .unwrap()
+        }
     }
 
     async fn connect_subscriber(
         server_port: u16,
         topic: String,
         messages_queue: MessageQueue,
+    ) -> JoinHandle<bool> {
+        connect_subscriber_with_options(server_port, topic, messages_queue, vec![], 0.0).await
+    }
+
+    async fn connect_subscriber_with_options(
+        server_port: u16,
+        topic: String,
+        messages_queue: MessageQueue,
+        options: Vec<(&'static str, Value)>,
+        connect_delay: f64, // in seconds
     ) -> JoinHandle<bool> {
         tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs_f64(connect_delay)).await;
             let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
             let subscriber = Subscriber::new(&socket_addr).await;
-            let mut subscriber_tester = SubscriberTester::new(subscriber, DELIMITER);
+            let mut subscriber_tester = options.into_iter().fold(
+                SubscriberTester::new(subscriber, DELIMITER),
+                |subscriber_tester, (key, value)| subscriber_tester.with_option(key, value),
+            );
             subscriber_tester
                 .test_messages(topic, messages_queue, LISTEN_WAIT_TIME)
                 .await
@@ -78,13 +250,23 @@ mod integration_tests {
         })
     }
 
+    async fn join_group(server_port: u16, topic: String, group: &str) -> Subscriber {
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        let mut options = Map::new();
+        options.insert("group".to_string(), json!(group));
+        options.insert("start".to_string(), json!("earliest"));
+        assert!(subscriber.auth(topic, &options, DELIMITER).await);
+        subscriber
+    }
+
     #[tokio::test]
     async fn init_test() {
         setup_logs();
         let server_details = gen_server_details();
         let server = create_server(server_details).await;
         let server_task = run_server(server).await;
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -108,7 +290,7 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -132,7 +314,7 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -172,7 +354,7 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -198,7 +380,7 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -241,7 +423,7 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -272,7 +454,7 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
     }
 
     #[tokio::test]
@@ -305,8 +487,8 @@ mod integration_tests {
         let sub_t1_message_queue = pub_t1_message_queue.union(&pub2_t1_message_queue);
         let sub_t2_message_queue = pub1_t2_message_queue.union(&pub2_t2_message_queue);
 
-        let topic1 = "topic 1".to_string();
-        let topic2 = "topic 2".to_string();
+        let topic1 = "topic_1".to_string();
+        let topic2 = "topic_2".to_string();
 
         assert_join(
             join!(
@@ -323,6 +505,1642 @@ mod integration_tests {
         )
         .await;
 
-        server_task.abort();
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn replay_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let message_queue = MessageQueue::new(vec![
+            Message::new("test message 1".to_string(), 1.0),
+            Message::new("test message 2".to_string(), 2.0),
+        ]);
+        let offset_message_queue =
+            MessageQueue::new(vec![Message::new("test message 2".to_string(), 2.0)]);
+        let topic = "test_topic".to_string();
+
+        assert_join(
+            join!(
+                connect_publisher(server_port, topic.clone(), message_queue.clone()),
+                connect_subscriber_with_options(
+                    server_port,
+                    topic.clone(),
+                    message_queue.clone(),
+                    vec![("start", json!("earliest"))],
+                    2.5,
+                ),
+                connect_subscriber_with_options(
+                    server_port,
+                    topic.clone(),
+                    offset_message_queue,
+                    vec![("start", json!({"offset": 1}))],
+                    2.5,
+                ),
+                connect_subscriber_with_options(
+                    server_port,
+                    topic.clone(),
+                    MessageQueue::default(),
+                    vec![("start", json!("latest"))],
+                    2.5,
+                ),
+            )
+            .to_vec(),
+        )
+        .await;
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn restart_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let data_dir = gen_data_dir("restart");
+        let topic = "test_topic".to_string();
+
+        let server = create_server(server_details)
+            .await
+            .with_data_dir(data_dir.clone());
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let message_queue = MessageQueue::new(vec![Message::new("test message".to_string(), 1.0)]);
+        assert_join(vec![
+            connect_publisher(server_port, topic.clone(), message_queue).await,
+        ])
+        .await;
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        server_task.stop().await;
+
+        let server = create_server(server_details)
+            .await
+            .with_data_dir(data_dir.clone());
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let message_queue = MessageQueue::new(vec![Message::new("test message".to_string(), 0.0)]);
+        assert_join(vec![
+            connect_subscriber_with_options(
+                server_port,
+                topic.clone(),
+                message_queue,
+                vec![("start", json!("earliest"))],
+                0.0,
+            )
+            .await,
+        ])
+        .await;
+
+        server_task.stop().await;
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn group_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let topic = "test_topic".to_string();
+        let mut message_queue = MessageQueue::default();
+        for i in 1..=6 {
+            message_queue.push(Message::new(
+                format!("test message {i}"),
+                1.0 + i as f64 / 10.0,
+            ));
+        }
+        let expected_messages: Vec<String> = message_queue
+            .messages
+            .iter()
+            .map(|message| message.message.clone())
+            .collect();
+
+        let mut first = join_group(server_port, topic.clone(), "workers").await;
+        let mut second = join_group(server_port, topic.clone(), "workers").await;
+        let publisher_task = connect_publisher(server_port, topic.clone(), message_queue).await;
+
+        let (mut first_messages, second_messages) = join!(
+            first.read_messages(DELIMITER, LISTEN_WAIT_TIME),
+            second.read_messages(DELIMITER, LISTEN_WAIT_TIME),
+        );
+        assert_join(vec![publisher_task]).await;
+
+        first_messages.extend(second_messages);
+        first_messages.sort();
+        assert_eq!(first_messages, expected_messages);
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn group_commit_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let topic = "test_topic".to_string();
+        let message_queue = MessageQueue::new(vec![
+            Message::new("test message 1".to_string(), 0.1),
+            Message::new("test message 2".to_string(), 0.2),
+            Message::new("test message 3".to_string(), 0.3),
+        ]);
+        assert_join(vec![
+            connect_publisher(server_port, topic.clone(), message_queue).await,
+        ])
+        .await;
+
+        let mut first = join_group(server_port, topic.clone(), "workers").await;
+        assert_eq!(
+            first.read_messages(DELIMITER, 1.0).await,
+            vec!["test message 1", "test message 2", "test message 3"]
+        );
+        assert!(
+            first
+                .send_message(json!({"method": "commit", "offset": 1}), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        drop(first);
+
+        let mut second = join_group(server_port, topic.clone(), "workers").await;
+        assert_eq!(
+            second.read_messages(DELIMITER, 1.0).await,
+            vec!["test message 3"]
+        );
+        assert!(
+            second
+                .send_message(json!({"method": "commit"}), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        drop(second);
+
+        let mut third = join_group(server_port, topic.clone(), "workers").await;
+        assert!(third.read_messages(DELIMITER, 1.0).await.is_empty());
+
+        server_task.stop().await;
+    }
+
+    async fn check_slow_consumer(server_port: u16, policy: &str) -> Vec<String> {
+        const MESSAGES_COUNT: usize = 300;
+        let topic = format!("{policy}_topic");
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        let mut options = Map::new();
+        options.insert(
+            "config".to_string(),
+            json!({ "slow_consumer_policy": policy }),
+        );
+        assert!(subscriber.auth(topic.clone(), &options, DELIMITER).await);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let mut message_queue = MessageQueue::default();
+        for i in 0..MESSAGES_COUNT {
+            message_queue.push(Message::new(format!("{i:065535}"), 0.0));
+        }
+        let publisher_task = connect_publisher(server_port, topic, message_queue).await;
+        tokio::time::sleep(Duration::from_secs_f64(2.0)).await;
+
+        let messages = subscriber.read_messages(DELIMITER, 2.0).await;
+        assert_join(vec![publisher_task]).await;
+        messages
+    }
+
+    #[tokio::test]
+    async fn slow_consumer_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+
+        let server = create_server(server_details)
+            .await
+            .with_acl_config(admin_acl("*"));
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let messages = check_slow_consumer(server_port, "drop_oldest").await;
+        let skipped: Vec<u64> = messages
+            .iter()
+            .filter_map(|message| serde_json::from_str::<Value>(message).ok())
+            .map(|message| {
+                assert_eq!(message["type"], "messages_skipped");
+                message["data"]["count"].as_u64().unwrap()
+            })
+            .collect();
+        assert!(!skipped.is_empty());
+        assert_eq!(
+            messages.len() - skipped.len() + skipped.iter().sum::<u64>() as usize,
+            300
+        );
+
+        let messages = check_slow_consumer(server_port, "disconnect").await;
+        assert!(messages.len() < 300);
+        assert_eq!(
+            messages.last().unwrap(),
+            r#"{"type":"disconnected","data":{"reason":"slow_consumer"}}"#
+        );
+
+        let messages = check_slow_consumer(server_port, "block").await;
+        let expected_messages: Vec<String> = (0..300).map(|i| format!("{i:065535}")).collect();
+        assert!(messages == expected_messages);
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn topics_config_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+
+        let topics_config = TopicsConfig {
+            topics: HashMap::from([(
+                "declared_topic".to_string(),
+                TopicConfig {
+                    max_message_size: Some(16),
+                    ..TopicConfig::default()
+                },
+            )]),
+            auto_create: false,
+            ..TopicsConfig::default()
+        };
+        let server = create_server(server_details)
+            .await
+            .with_topics_config(topics_config);
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .auth("unknown_topic".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert_eq!(
+            subscriber.read_error(DELIMITER).await,
+            Some("unknown_topic".to_string())
+        );
+
+        let publisher_message_queue = MessageQueue::new(vec![
+            Message::new("short message".to_string(), 1.0),
+            Message::new("too long message for the topic".to_string(), 2.0),
+            Message::new("short message 2".to_string(), 3.0),
+        ]);
+        let subscriber_message_queue = MessageQueue::new(vec![
+            Message::new("short message".to_string(), 1.0),
+            Message::new("short message 2".to_string(), 3.0),
+        ]);
+        let topic = "declared_topic".to_string();
+
+        assert_join(
+            join!(
+                connect_publisher(server_port, topic.clone(), publisher_message_queue),
+                connect_subscriber(server_port, topic.clone(), subscriber_message_queue),
+            )
+            .to_vec(),
+        )
+        .await;
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn framing_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await.with_limits(Limits {
+            max_frame_size: 128,
+            ..Limits::default()
+        });
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "framing_topic".to_string();
+        let mut options = Map::new();
+        options.insert("framing".to_string(), json!("length_prefixed"));
+
+        let mut framed_subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            framed_subscriber
+                .auth(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        let mut line_subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            line_subscriber
+                .auth(topic.clone(), &Map::new(), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let payloads: Vec<Vec<u8>> = vec![
+            b"text message".to_vec(),
+            b"multi\nline\nmessage".to_vec(),
+            vec![0, 255, 10, 13, 0],
+            vec![],
+        ];
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        for payload in &payloads {
+            assert!(publisher.send_frame(payload).await);
+        }
+
+        for payload in &payloads {
+            assert_eq!(
+                framed_subscriber.read_frame(LISTEN_WAIT_TIME).await,
+                Some(payload.clone())
+            );
+        }
+        assert_eq!(
+            line_subscriber.read_message(DELIMITER).await,
+            Some("text message\n".to_string())
+        );
+
+        // messages bigger than max_frame_size disconnect the client
+        assert!(publisher.send_frame(&[0; 129]).await);
+        assert_eq!(publisher.read_reply(DELIMITER).await, None);
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .send_message(
+                    json!({"method": "subscribe", "topic": topic, "name": "a".repeat(128)}),
+                    DELIMITER
+                )
+                .await
+        );
+        assert_eq!(
+            subscriber.read_error(DELIMITER).await,
+            Some("unexpected_message".to_string())
+        );
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn message_format_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "format_topic".to_string();
+        let mut options = Map::new();
+        options.insert("format".to_string(), json!("json"));
+
+        let mut json_subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            json_subscriber
+                .auth(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        let mut raw_subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            raw_subscriber
+                .auth(topic.clone(), &Map::new(), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let mut json_publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            json_publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        assert!(
+            json_publisher
+                .send_message(json!("not a publisher message"), DELIMITER)
+                .await
+        );
+        assert!(
+            json_publisher
+                .send_message(
+                    json!({
+                        "method": "send_message",
+                        "text": "Test message",
+                        "key": "user-42",
+                        "headers": {"trace_id": "a1b2", "content_type": "text/plain"}
+                    }),
+                    DELIMITER
+                )
+                .await
+        );
+
+        let message: Value =
+            serde_json::from_str(&json_subscriber.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(message["type"], "message");
+        assert_eq!(message["data"]["from_topic"], "format_topic");
+        assert_eq!(message["data"]["message_text"], "Test message");
+        assert_eq!(message["data"]["key"], "user-42");
+        assert_eq!(
+            message["data"]["headers"],
+            json!({"trace_id": "a1b2", "content_type": "text/plain"})
+        );
+        assert!(message["data"]["publisher_id"].is_u64());
+        assert!(message["data"]["time"].as_u64().unwrap() > 0);
+
+        assert_eq!(
+            raw_subscriber.read_messages(DELIMITER, 1.0).await,
+            vec!["Test message".to_string()]
+        );
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn ack_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details)
+            .await
+            .with_acl_config(admin_acl("*"));
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "ack_topic".to_string();
+        let mut options = Map::new();
+        options.insert("ack".to_string(), json!("message"));
+        options.insert("config".to_string(), json!({"max_message_size": 8}));
+
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        assert!(publisher.send_lines(&["first"], DELIMITER).await);
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await,
+            Some(json!({"type": "ack", "data": {"sequence": 0, "offset": 0}}))
+        );
+        assert!(publisher.send_lines(&["too long message"], DELIMITER).await);
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await,
+            Some(json!({"type": "ack", "data": {"sequence": 1, "error": "message_too_large"}}))
+        );
+
+        options.insert("ack".to_string(), json!("batch"));
+        let mut batch_publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            batch_publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        assert!(
+            batch_publisher
+                .send_lines(&["second", "too long message", "third"], DELIMITER)
+                .await
+        );
+        assert_eq!(
+            batch_publisher.read_reply(DELIMITER).await,
+            Some(json!({"type": "batch_ack", "data": {"acks": [
+                {"sequence": 0, "offset": 1},
+                {"sequence": 1, "error": "message_too_large"},
+                {"sequence": 2, "offset": 2}
+            ]}}))
+        );
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn multiple_topic_connection_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut options = Map::new();
+        options.insert("topics".to_string(), json!(["second_topic"]));
+        options.insert("format".to_string(), json!("json"));
+
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .auth("first_topic".to_string(), &options, DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        options.insert("ack".to_string(), json!("message"));
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options("first_topic".to_string(), &options, DELIMITER)
+                .await
+        );
+        for (text, topic) in [
+            ("first message", None),
+            ("second message", Some("second_topic")),
+            ("third message", Some("third_topic")),
+        ] {
+            assert!(
+                publisher
+                    .send_message(
+                        json!({"method": "send_message", "text": text, "topic": topic}),
+                        DELIMITER
+                    )
+                    .await
+            );
+        }
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await.unwrap()["data"],
+            json!({"sequence": 0, "offset": 0})
+        );
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await.unwrap()["data"],
+            json!({"sequence": 1, "offset": 0})
+        );
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await.unwrap()["data"],
+            json!({"sequence": 2, "error": "unknown_topic"})
+        );
+
+        let mut received = vec![];
+        for _ in 0..2 {
+            let message: Value =
+                serde_json::from_str(&subscriber.read_message(DELIMITER).await.unwrap()).unwrap();
+            received.push((
+                message["data"]["from_topic"].as_str().unwrap().to_string(),
+                message["data"]["message_text"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            ));
+        }
+        received.sort();
+        assert_eq!(
+            received,
+            vec![
+                ("first_topic".to_string(), "first message".to_string()),
+                ("second_topic".to_string(), "second message".to_string()),
+            ]
+        );
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn wildcard_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut kitchen_publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            kitchen_publisher
+                .auth_with_options(
+                    "sensors/kitchen/temperature".to_string(),
+                    &Map::new(),
+                    DELIMITER
+                )
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let mut options = Map::new();
+        options.insert("format".to_string(), json!("json"));
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .auth("sensors/+/temperature".to_string(), &options, DELIMITER)
+                .await
+        );
+        let mut invalid_subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            invalid_subscriber
+                .auth("sensors/#/temperature".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert_eq!(
+            invalid_subscriber.read_error(DELIMITER).await,
+            Some("unexpected_message".to_string())
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        assert!(kitchen_publisher.send_lines(&["21.5"], DELIMITER).await);
+        for (topic, message) in [
+            ("sensors/hall/humidity", "40"),
+            ("sensors/hall/temperature", "19.0"),
+        ] {
+            let mut publisher = Publisher::new(&socket_addr).await;
+            assert!(
+                publisher
+                    .auth_with_options(topic.to_string(), &Map::new(), DELIMITER)
+                    .await
+            );
+            tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+            assert!(publisher.send_lines(&[message], DELIMITER).await);
+        }
+
+        let mut received = vec![];
+        for _ in 0..2 {
+            let message: Value =
+                serde_json::from_str(&subscriber.read_message(DELIMITER).await.unwrap()).unwrap();
+            received.push((
+                message["data"]["from_topic"].as_str().unwrap().to_string(),
+                message["data"]["message_text"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            ));
+        }
+        received.sort();
+        assert_eq!(
+            received,
+            vec![
+                ("sensors/hall/temperature".to_string(), "19.0".to_string()),
+                (
+                    "sensors/kitchen/temperature".to_string(),
+                    "21.5".to_string()
+                ),
+            ]
+        );
+        assert!(subscriber.read_messages(DELIMITER, 1.0).await.is_empty());
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn topic_name_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        for topic in [
+            "",
+            "ab",
+            "topic with spaces",
+            "topic_name_that_is_too_long_for_the_default_topic_name_pattern_of_server",
+        ] {
+            let mut subscriber = Subscriber::new(&socket_addr).await;
+            assert!(
+                subscriber
+                    .auth(topic.to_string(), &Map::new(), DELIMITER)
+                    .await
+            );
+            assert_eq!(
+                subscriber.read_error(DELIMITER).await,
+                Some("invalid_topic_name".to_string())
+            );
+        }
+
+        let message_queue = MessageQueue::new(vec![Message::new("test message".to_string(), 1.0)]);
+        let topic = "valid-topic_name".to_string();
+        assert_join(
+            join!(
+                connect_publisher(server_port, topic.clone(), message_queue.clone()),
+                connect_subscriber(server_port, topic.clone(), message_queue.clone()),
+            )
+            .to_vec(),
+        )
+        .await;
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn client_name_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "named_topic".to_string();
+        let mut options = Map::new();
+        options.insert("format".to_string(), json!("json"));
+
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &options, DELIMITER).await);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        options.insert("name".to_string(), json!("sensor"));
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        let mut duplicate = Subscriber::new(&socket_addr).await;
+        assert!(duplicate.auth(topic.clone(), &options, DELIMITER).await);
+        assert_eq!(
+            duplicate.read_error(DELIMITER).await,
+            Some("duplicate_name".to_string())
+        );
+
+        assert!(
+            publisher
+                .send_message(
+                    json!({"method": "send_message", "text": "Test message"}),
+                    DELIMITER
+                )
+                .await
+        );
+        let message: Value =
+            serde_json::from_str(&subscriber.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(message["data"]["publisher_name"], "sensor");
+        assert!(message["data"]["publisher_id"].is_u64());
+
+        // the name is released when the client disconnects
+        drop(publisher);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        assert!(
+            publisher
+                .send_message(
+                    json!({"method": "send_message", "text": "Second message"}),
+                    DELIMITER
+                )
+                .await
+        );
+        let message: Value =
+            serde_json::from_str(&subscriber.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(message["data"]["message_text"], "Second message");
+        assert_eq!(message["data"]["publisher_name"], "sensor");
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn presence_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "presence_topic".to_string();
+        let mut options = Map::new();
+        options.insert("presence".to_string(), json!(true));
+        options.insert("name".to_string(), json!("watcher"));
+
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &Map::new(), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let mut watcher = Subscriber::new(&socket_addr).await;
+        assert!(watcher.auth(topic.clone(), &options, DELIMITER).await);
+        let info: Value =
+            serde_json::from_str(&watcher.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(info["type"], "connection_info");
+        assert_eq!(info["data"]["me"]["name"], "watcher");
+        assert_eq!(info["data"]["me"]["role"], "subscriber");
+        let publishers = info["data"]["topics"]["presence_topic"]["publishers"]
+            .as_array()
+            .unwrap();
+        assert_eq!(publishers.len(), 1);
+        let publisher_id = publishers[0]["id"].clone();
+
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &Map::new(), DELIMITER).await);
+        let event: Value =
+            serde_json::from_str(&watcher.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(event["type"], "new_subscriber");
+        assert_eq!(event["data"]["connected_topics"], json!(["presence_topic"]));
+
+        drop(publisher);
+        let event: Value =
+            serde_json::from_str(&watcher.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(event["type"], "publisher_left");
+        assert_eq!(event["data"]["publisher"]["id"], publisher_id);
+
+        // clients without presence events receive only messages
+        assert!(subscriber.read_messages(DELIMITER, 0.5).await.is_empty());
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn admin_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details)
+            .await
+            .with_acl_config(admin_acl("*"));
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut admin = Subscriber::new(&socket_addr).await;
+        assert!(
+            admin
+                .send_message(json!({"method": "admin", "name": "on-call"}), DELIMITER)
+                .await
+        );
+
+        assert!(
+            admin
+                .send_message(
+                    json!({"method": "create_topic", "topic": "admin_topic", "config": {"max_message_size": 64}}),
+                    DELIMITER
+                )
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["type"], "topic");
+        assert_eq!(reply["data"]["name"], "admin_topic");
+        assert_eq!(reply["data"]["config"]["max_message_size"], 64);
+        assert!(
+            admin
+                .send_message(
+                    json!({"method": "create_topic", "topic": "admin_topic"}),
+                    DELIMITER
+                )
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["data"]["code"], "topic_exists");
+
+        let topic = "admin_topic".to_string();
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &Map::new(), DELIMITER).await);
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &Map::new(), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        assert!(
+            admin
+                .send_message(json!({"method": "list_connections"}), DELIMITER)
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        let connections = reply["data"]["connections"].as_array().unwrap();
+        assert_eq!(connections.len(), 3);
+        assert_eq!(connections[0]["role"], "admin");
+        assert_eq!(connections[0]["name"], "on-call");
+        assert_eq!(connections[2]["role"], "publisher");
+        let publisher_id = connections[2]["id"].clone();
+
+        assert!(
+            admin
+                .send_message(
+                    json!({"method": "describe_topic", "topic": "admin_topic"}),
+                    DELIMITER
+                )
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["data"]["subscribers"].as_array().unwrap().len(), 1);
+        assert_eq!(reply["data"]["publishers"][0]["id"], publisher_id);
+
+        assert!(
+            admin
+                .send_message(
+                    json!({"method": "disconnect_client", "id": publisher_id}),
+                    DELIMITER
+                )
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["type"], "client_disconnected");
+        let message = publisher.read_reply(DELIMITER).await.unwrap();
+        assert_eq!(message["type"], "disconnected");
+        assert_eq!(message["data"]["reason"], "admin");
+
+        assert!(
+            admin
+                .send_message(
+                    json!({"method": "delete_topic", "topic": "admin_topic"}),
+                    DELIMITER
+                )
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["type"], "topic_deleted");
+        let message: Value =
+            serde_json::from_str(&subscriber.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(message["data"]["reason"], "topic_deleted");
+
+        assert!(
+            admin
+                .send_message(json!({"method": "list_topics"}), DELIMITER)
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["data"]["topics"], json!([]));
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn shutdown_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await;
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "shutdown_topic".to_string();
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &Map::new(), DELIMITER).await);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let mut options = Map::new();
+        options.insert("ack".to_string(), json!("batch"));
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        assert!(
+            publisher
+                .send_lines(&["first", "second", "third"], DELIMITER)
+                .await
+        );
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await.unwrap()["type"],
+            "batch_ack"
+        );
+
+        // an idle connection without connection message does not delay the shutdown
+        let _idle = Subscriber::new(&socket_addr).await;
+        server_task.stop().await;
+
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await,
+            Some(json!({"type": "disconnected", "data": {"reason": "shutdown"}}))
+        );
+        let mut messages = subscriber.read_messages(DELIMITER, 0.5).await;
+        let disconnected: Value = serde_json::from_str(&messages.pop().unwrap()).unwrap();
+        assert_eq!(messages, vec!["first", "second", "third"]);
+        assert_eq!(
+            disconnected,
+            json!({"type": "disconnected", "data": {"reason": "shutdown"}})
+        );
+        assert!(!subscriber.wait_message().await);
+        assert!(tokio::net::TcpStream::connect(socket_addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn limits_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details).await.with_limits(Limits {
+            max_connections: Some(1),
+            ..Limits::default()
+        });
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "limited_topic".to_string();
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &Map::new(), DELIMITER).await);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let mut rejected = Subscriber::new(&socket_addr).await;
+        assert_eq!(
+            rejected.read_error(DELIMITER).await,
+            Some("too_many_connections".to_string())
+        );
+
+        // the connection is released when the client disconnects
+        drop(subscriber);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &Map::new(), DELIMITER).await);
+        assert!(subscriber.read_messages(DELIMITER, 0.5).await.is_empty());
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn reload_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details)
+            .await
+            .with_acl_config(admin_acl("*"));
+        let server_port = server.port();
+        let config = Arc::new(Mutex::new(ServerConfig {
+            listener: ListenerConfig {
+                address: server_details.ip(),
+                port: server_details.port(),
+                enabled: true,
+            },
+            acl: admin_acl("*"),
+            ..ServerConfig::default()
+        }));
+        let loaded_config = config.clone();
+        let server = server.with_config_loader(move || Ok(loaded_config.lock().unwrap().clone()));
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "reloaded_topic".to_string();
+        let mut options = Map::new();
+        options.insert("ack".to_string(), json!("message"));
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        assert!(publisher.send_lines(&["long message"], DELIMITER).await);
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await,
+            Some(json!({"type": "ack", "data": {"sequence": 0, "offset": 0}}))
+        );
+
+        {
+            let mut config = config.lock().unwrap();
+            config.listener.port = 1;
+            config.limits.max_connections = Some(3);
+            config.topics.topics.insert(
+                topic.clone(),
+                TopicConfig {
+                    max_message_size: Some(8),
+                    ..TopicConfig::default()
+                },
+            );
+        }
+        let mut admin = Subscriber::new(&socket_addr).await;
+        assert!(
+            admin
+                .send_message(json!({"method": "admin"}), DELIMITER)
+                .await
+        );
+        assert!(
+            admin
+                .send_message(json!({"method": "reload_config"}), DELIMITER)
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        assert_eq!(reply["type"], "config_reloaded");
+        assert_eq!(reply["data"]["applied"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            reply["data"]["restart_required"].as_array().unwrap().len(),
+            1
+        );
+
+        // settings of the topic are applied to connected publishers
+        assert!(publisher.send_lines(&["long message"], DELIMITER).await);
+        assert_eq!(
+            publisher.read_reply(DELIMITER).await,
+            Some(json!({"type": "ack", "data": {"sequence": 1, "error": "message_too_large"}}))
+        );
+
+        // the publisher, the admin and one more client
+        let _subscriber = Subscriber::new(&socket_addr).await;
+        let mut rejected = Subscriber::new(&socket_addr).await;
+        assert_eq!(
+            rejected.read_error(DELIMITER).await,
+            Some("too_many_connections".to_string())
+        );
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn metrics_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details)
+            .await
+            .with_http_listener(&server_details)
+            .await
+            .unwrap();
+        let server_port = server.port();
+        let http_port = server.http_port().unwrap();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "metered_topic".to_string();
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &Map::new(), DELIMITER).await);
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert!(publisher.send_lines(&["first", "second"], DELIMITER).await);
+        assert_eq!(subscriber.read_messages(DELIMITER, 0.5).await.len(), 2);
+        let mut invalid = Subscriber::new(&socket_addr).await;
+        assert!(
+            invalid
+                .send_message(json!({"method": "unknown"}), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        let (status, body) = http_get(http_port, "/metrics").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        for sample in [
+            r#"simple_kafka_messages_in_total{topic="metered_topic"} 2"#,
+            r#"simple_kafka_bytes_in_total{topic="metered_topic"} 11"#,
+            r#"simple_kafka_messages_out_total{topic="metered_topic"} 2"#,
+            r#"simple_kafka_bytes_out_total{topic="metered_topic"} 11"#,
+            r#"simple_kafka_connected_clients{role="publisher"} 1"#,
+            r#"simple_kafka_connected_clients{role="subscriber"} 1"#,
+            r#"simple_kafka_connection_errors_total{error="unexpected_message"} 1"#,
+            "simple_kafka_connections_total 3",
+        ] {
+            assert!(body.lines().any(|line| line == sample), "{}", sample);
+        }
+
+        let (status, _) = http_get(http_port, "/unknown").await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn health_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let data_dir = gen_data_dir("health");
+        let server = create_server(server_details)
+            .await
+            .with_data_dir(&data_dir)
+            .with_http_listener(&server_details)
+            .await
+            .unwrap();
+        let server_port = server.port();
+        let http_port = server.http_port().unwrap();
+        let server_task = run_server(server).await;
+
+        assert_eq!(
+            http_get(http_port, "/healthz").await,
+            ("HTTP/1.1 200 OK".to_string(), "ok\n".to_string())
+        );
+        assert_eq!(
+            http_get(http_port, "/readyz?verbose").await,
+            ("HTTP/1.1 200 OK".to_string(), "ready\n".to_string())
+        );
+
+        // the server accepts clients when it is ready
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .auth("health_topic".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert!(subscriber.read_messages(DELIMITER, 0.5).await.is_empty());
+
+        server_task.stop().await;
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn auth_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let users_file = gen_data_dir("users").with_extension("toml");
+        std::fs::write(
+            &users_file,
+            format!("alice = \"{}\"\n", hash_password("secret")),
+        )
+        .unwrap();
+        let server = create_server(server_details)
+            .await
+            .with_auth_config(AuthConfig {
+                users_file: Some(users_file.clone()),
+                tokens: BTreeMap::from([("ci".to_string(), "ci-token".to_string())]),
+                allow_anonymous: false,
+            })
+            .with_acl_config(admin_acl("ci"));
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let topic = "secured_topic".to_string();
+        for credentials in [
+            None,
+            Some(json!({"username": "alice", "password": "wrong"})),
+            Some(json!({"username": "bob", "password": "secret"})),
+            Some(json!({"token": "wrong"})),
+        ] {
+            let mut options = Map::new();
+            if let Some(credentials) = credentials {
+                options.insert("auth".to_string(), credentials);
+            }
+            let mut rejected = Subscriber::new(&socket_addr).await;
+            assert!(rejected.auth(topic.clone(), &options, DELIMITER).await);
+            assert_eq!(
+                rejected.read_error(DELIMITER).await,
+                Some("authentication_failed".to_string())
+            );
+        }
+
+        // passwords are verified slowly, the message can be published before the subscriber is connected
+        let mut options = Map::new();
+        options.insert(
+            "auth".to_string(),
+            json!({"username": "alice", "password": "secret"}),
+        );
+        options.insert("start".to_string(), json!("earliest"));
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(subscriber.auth(topic.clone(), &options, DELIMITER).await);
+        let mut options = Map::new();
+        options.insert("auth".to_string(), json!({"token": "ci-token"}));
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(topic.clone(), &options, DELIMITER)
+                .await
+        );
+        assert!(publisher.send_lines(&["authenticated"], DELIMITER).await);
+        assert_eq!(
+            subscriber.read_messages(DELIMITER, 2.0).await,
+            vec!["authenticated".to_string()]
+        );
+
+        let mut admin = Subscriber::new(&socket_addr).await;
+        assert!(
+            admin
+                .send_message(
+                    json!({"method": "admin", "auth": {"token": "ci-token"}}),
+                    DELIMITER
+                )
+                .await
+        );
+        assert!(
+            admin
+                .send_message(json!({"method": "list_connections"}), DELIMITER)
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        let principals: Vec<&Value> = reply["data"]["connections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|connection| &connection["principal"])
+            .collect();
+        assert_eq!(principals, vec!["alice", "ci", "ci"]);
+
+        server_task.stop().await;
+        std::fs::remove_file(users_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn acl_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let server = create_server(server_details)
+            .await
+            .with_auth_config(AuthConfig {
+                tokens: BTreeMap::from([
+                    ("billing".to_string(), "billing-token".to_string()),
+                    ("sales".to_string(), "sales-token".to_string()),
+                ]),
+                ..AuthConfig::default()
+            })
+            .with_acl_config(AclConfig {
+                default: AclDefault::Deny,
+                rules: vec![
+                    AclRule {
+                        principal: "billing".to_string(),
+                        topics: vec!["billing.*".to_string(), "public".to_string()],
+                        permissions: vec![Permission::Publish, Permission::Subscribe],
+                    },
+                    AclRule {
+                        principal: "*".to_string(),
+                        topics: vec!["public".to_string()],
+                        permissions: vec![Permission::Subscribe],
+                    },
+                ],
+            });
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let options = |token: &str| {
+            let mut options = Map::new();
+            options.insert("auth".to_string(), json!({ "token": token }));
+            options.insert("start".to_string(), json!("earliest"));
+            options
+        };
+
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options(
+                    "billing.eu".to_string(),
+                    &options("billing-token"),
+                    DELIMITER
+                )
+                .await
+        );
+        assert!(publisher.send_lines(&["invoice"], DELIMITER).await);
+        let mut publisher = Publisher::new(&socket_addr).await;
+        assert!(
+            publisher
+                .auth_with_options("public".to_string(), &options("billing-token"), DELIMITER)
+                .await
+        );
+        assert!(publisher.send_lines(&["announcement"], DELIMITER).await);
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+
+        for (topic, method) in [("billing.eu", "subscribe"), ("public", "publish")] {
+            let mut denied = Subscriber::new(&socket_addr).await;
+            let mut connection_message = json!({"method": method, "topic": topic});
+            connection_message
+                .as_object_mut()
+                .unwrap()
+                .extend(options("sales-token"));
+            assert!(denied.send_message(connection_message, DELIMITER).await);
+            assert_eq!(
+                denied.read_error(DELIMITER).await,
+                Some("access_denied".to_string())
+            );
+        }
+        // only admins configure topics
+        let mut denied = Subscriber::new(&socket_addr).await;
+        assert!(
+            denied
+                .send_message(
+                    json!({"method": "publish", "topic": "billing.us", "auth": {"token": "billing-token"},
+                        "config": {"channel_capacity": 65536}}),
+                    DELIMITER
+                )
+                .await
+        );
+        assert_eq!(
+            denied.read_error(DELIMITER).await,
+            Some("access_denied".to_string())
+        );
+        let mut denied = Subscriber::new(&socket_addr).await;
+        assert!(
+            denied
+                .send_message(
+                    json!({"method": "admin", "auth": {"token": "billing-token"}}),
+                    DELIMITER
+                )
+                .await
+        );
+        assert_eq!(
+            denied.read_error(DELIMITER).await,
+            Some("access_denied".to_string())
+        );
+
+        // topic filters match only allowed topics, denied matching topics are reported
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .auth("#".to_string(), &options("sales-token"), DELIMITER)
+                .await
+        );
+        let mut messages = subscriber.read_messages(DELIMITER, 0.5).await;
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec![
+                "announcement".to_string(),
+                r#"{"type":"error","data":{"code":"access_denied","message":"principal \"sales\" cannot subscribe to topic \"billing.eu\""}}"#
+                    .to_string()
+            ]
+        );
+        let mut subscriber = Subscriber::new(&socket_addr).await;
+        assert!(
+            subscriber
+                .auth(
+                    "billing.eu".to_string(),
+                    &options("billing-token"),
+                    DELIMITER
+                )
+                .await
+        );
+        assert_eq!(
+            subscriber.read_messages(DELIMITER, 0.5).await,
+            vec!["invoice".to_string()]
+        );
+
+        server_task.stop().await;
+    }
+
+    #[tokio::test]
+    async fn tls_test() {
+        setup_logs();
+        let server_details = gen_server_details();
+        let pki = TestPki::new("tls");
+        let server = create_server(server_details)
+            .await
+            .with_tls_config(pki.server_config())
+            .unwrap()
+            .with_acl_config(AclConfig {
+                default: AclDefault::Deny,
+                rules: vec![AclRule {
+                    principal: "billing".to_string(),
+                    topics: vec!["billing".to_string()],
+                    permissions: vec![Permission::Publish, Permission::Subscribe],
+                }],
+            });
+        let server_port = server.port();
+        let server_task = run_server(server).await;
+
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), server_port);
+        let mut options = Map::new();
+        options.insert("start".to_string(), json!("earliest"));
+
+        // clients are authenticated as the subject of their certificates
+        let mut subscriber =
+            Subscriber::with_stream(pki.connect(&socket_addr, Some("billing")).await);
+        assert!(
+            subscriber
+                .auth("billing".to_string(), &options, DELIMITER)
+                .await
+        );
+        let mut publisher =
+            Publisher::with_stream(pki.connect(&socket_addr, Some("billing")).await);
+        assert!(
+            publisher
+                .auth_with_options("billing".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert!(
+            publisher
+                .send_lines(&["invoice", "receipt"], DELIMITER)
+                .await
+        );
+        assert_eq!(
+            subscriber.read_messages(DELIMITER, 1.0).await,
+            vec!["invoice".to_string(), "receipt".to_string()]
+        );
+
+        let mut denied = Subscriber::with_stream(pki.connect(&socket_addr, Some("sales")).await);
+        assert!(
+            denied
+                .auth("billing".to_string(), &options, DELIMITER)
+                .await
+        );
+        assert_eq!(
+            denied.read_error(DELIMITER).await,
+            Some("access_denied".to_string())
+        );
+
+        // without a client certificate and without TLS clients are not served
+        let mut anonymous = Subscriber::with_stream(pki.connect(&socket_addr, None).await);
+        let _ = anonymous
+            .auth("billing".to_string(), &options, DELIMITER)
+            .await;
+        assert!(!anonymous.wait_message().await);
+        let mut plain = Subscriber::new(&socket_addr).await;
+        assert!(plain.auth("billing".to_string(), &options, DELIMITER).await);
+        assert_eq!(plain.read_error(DELIMITER).await, None);
+
+        server_task.stop().await;
+        std::fs::remove_dir_all(pki.dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_listener_test() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        use tokio::net::UnixStream;
+
+        setup_logs();
+        let dir = gen_data_dir("unix_listener");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kafka.sock");
+        // the socket file of a crashed server is removed at startup
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let uid = std::fs::metadata(&dir).unwrap().uid();
+        let config = ServerConfig {
+            listener: ListenerConfig {
+                enabled: false,
+                ..ListenerConfig::default()
+            },
+            unix_listener: Some(UnixListenerConfig {
+                path: path.clone(),
+                mode: Some(0o600),
+            }),
+            acl: AclConfig {
+                default: AclDefault::Deny,
+                rules: vec![AclRule {
+                    principal: format!("uid:{}", uid),
+                    topics: vec!["local".to_string()],
+                    permissions: vec![
+                        Permission::Publish,
+                        Permission::Subscribe,
+                        Permission::Admin,
+                    ],
+                }],
+            },
+            ..ServerConfig::default()
+        };
+        let server = Server::from_config(&config).await.unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        // the socket is taken by the running server
+        assert_eq!(
+            Server::from_config(&config).await.err().map(|e| e.kind()),
+            Some(io::ErrorKind::AddrInUse)
+        );
+        let server_task = run_server(server).await;
+
+        // clients are authenticated as the uid of their process
+        let mut subscriber = Subscriber::with_stream(UnixStream::connect(&path).await.unwrap());
+        assert!(
+            subscriber
+                .auth("local".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        tokio::time::sleep(Duration::from_secs_f64(0.5)).await;
+        let mut publisher = Publisher::with_stream(UnixStream::connect(&path).await.unwrap());
+        assert!(
+            publisher
+                .auth_with_options("local".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert!(publisher.send_lines(&["ping", "pong"], DELIMITER).await);
+        assert_eq!(
+            subscriber.read_messages(DELIMITER, 1.0).await,
+            vec!["ping".to_string(), "pong".to_string()]
+        );
+
+        let mut denied = Subscriber::with_stream(UnixStream::connect(&path).await.unwrap());
+        assert!(
+            denied
+                .auth("remote".to_string(), &Map::new(), DELIMITER)
+                .await
+        );
+        assert_eq!(
+            denied.read_error(DELIMITER).await,
+            Some("access_denied".to_string())
+        );
+
+        // connections show the credentials of the client process
+        let mut admin = Subscriber::with_stream(UnixStream::connect(&path).await.unwrap());
+        assert!(
+            admin
+                .send_message(json!({"method": "admin"}), DELIMITER)
+                .await
+        );
+        assert!(
+            admin
+                .send_message(json!({"method": "list_connections"}), DELIMITER)
+                .await
+        );
+        let reply: Value =
+            serde_json::from_str(&admin.read_message(DELIMITER).await.unwrap()).unwrap();
+        let connection = &reply["data"]["connections"][0];
+        assert_eq!(connection["principal"], format!("uid:{}", uid));
+        assert!(connection["address"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!("unix:uid={},", uid)));
+
+        server_task.stop().await;
+        assert!(!path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }